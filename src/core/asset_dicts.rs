@@ -0,0 +1,137 @@
+/*
+    Data types of the localized asset dicts (found in the assets dir).
+    Kept separate from the hooks so that they can be parsed without the game.
+    orig_* fields are only there for reference (see asset_skeleton.rs), they're never used.
+*/
+use fnv::FnvHashMap;
+use serde::{Deserialize, Serialize};
+
+// story/data/xx/yyyy/storytimeline_xxyyyyzzz.json
+// home/data/xxxxx/yy/hometimeline_xxxxx_yy_zzzzzzz.json
+// (Aliases are there for tlg compatibility)
+#[derive(Serialize, Deserialize, Default)]
+pub struct StoryTimelineDataDict {
+    #[serde(alias = "Title")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title_source_hash: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orig_title: Option<String>,
+
+    #[serde(alias = "TextBlockList")]
+    #[serde(default)]
+    pub text_block_list: Vec<TextBlockDict>,
+
+    #[serde(default)]
+    pub no_wrap: bool
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct TextBlockDict {
+    #[serde(alias = "Name")]
+    pub name: Option<String>,
+
+    #[serde(alias = "Text")]
+    pub text: Option<String>,
+
+    #[serde(alias = "ChoiceDataList")]
+    #[serde(default)]
+    pub choice_data_list: Vec<String>,
+
+    #[serde(alias = "ColorTextInfoList")]
+    #[serde(default)]
+    pub color_text_info_list: Vec<String>,
+
+    pub new_clip_length: Option<i32>,
+
+    // Fingerprint of the block's original text (see source_hash.rs)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source_hash: Option<String>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orig_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orig_text: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub orig_choice_data_list: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub orig_color_text_info_list: Vec<String>
+}
+
+// Either "text" or {"text": "...", "source_hash": "..."}
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum SourceHashedText {
+    Plain(String),
+    Hashed {
+        text: String,
+        source_hash: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        orig_text: Option<String>
+    }
+}
+
+impl SourceHashedText {
+    pub fn text(&self) -> &str {
+        match self {
+            SourceHashedText::Plain(text) | SourceHashedText::Hashed { text, .. } => text
+        }
+    }
+
+    pub fn source_hash(&self) -> Option<&str> {
+        match self {
+            SourceHashedText::Plain(_) => None,
+            SourceHashedText::Hashed { source_hash, .. } => source_hash.as_deref()
+        }
+    }
+}
+
+// race/storyrace/text/storyrace_xxxxxxxxx.json
+pub type StoryRaceTextDict = Vec<SourceHashedText>;
+
+// lyrics/mXXXX_lyrics.json
+pub type LyricsDict = FnvHashMap<i32, SourceHashedText>; // {"time_ms": "text"}
+
+// uianimation/flash/**.json (AssetInfo data)
+#[derive(Serialize, Deserialize, Default)]
+pub struct AnRootData {
+    #[serde(default)]
+    pub motion_parameter_list: FnvHashMap<i32, AnMotionParameterData>
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct AnMotionParameterData {
+    #[serde(default)]
+    pub text_param_list: FnvHashMap<i32, AnTextParameterData>
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct AnObjectParameterBaseData {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub position_offset: Option<Vector3>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scale: Option<Vector3>
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct AnTextParameterData {
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub orig_text: Option<String>,
+
+    #[serde(flatten)]
+    pub base: AnObjectParameterBaseData
+}
+
+// uianimation/flashcombine/**.json (AssetInfo data)
+#[derive(Serialize, Deserialize)]
+pub struct FlashActionPlayerData {
+    pub an_root: Option<AnRootData>
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Vector3 {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32
+}
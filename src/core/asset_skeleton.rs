@@ -0,0 +1,56 @@
+/*
+    Translator mode: loaded assets that don't have a dict yet are written out as untranslated skeletons,
+    at the same relative path that the asset dict would have (in the asset_skeletons dir of the data dir).
+    Translated fields are left empty and the original text is kept in the orig_* fields for reference,
+    so a skeleton can be copied into the assets dir and filled in as is. Race texts and lyrics are the
+    exception: empty entries overwrite the text there, so they start out with the original text.
+*/
+use std::{path::Path, sync::Mutex};
+
+use fnv::FnvHashSet;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use super::{utils, Error, Hachimi};
+
+pub const DIR: &str = "asset_skeletons";
+
+// Paths that have already been handled this session, saves a trip to the fs on every load
+static SEEN: Lazy<Mutex<FnvHashSet<String>>> = Lazy::new(|| Mutex::default());
+
+// Same layout as the asset dicts with AssetInfo data (flash, flashcombine)
+#[derive(Serialize)]
+pub struct SkeletonAssetInfo<T> {
+    pub data: T
+}
+
+pub fn is_enabled() -> bool {
+    Hachimi::instance().config.load().translator_mode
+}
+
+/// Writes the skeleton for the asset at `rel_path` (relative to the assets dir) if it doesn't exist yet.
+/// `build` is only called if the skeleton needs to be written; returning None skips the asset.
+pub fn save<T: Serialize>(rel_path: &str, build: impl FnOnce() -> Option<T>) {
+    if !is_enabled() || !SEEN.lock().unwrap().insert(rel_path.to_owned()) {
+        return;
+    }
+
+    let path = Hachimi::instance().get_data_path(DIR).join(rel_path);
+    if path.is_file() {
+        return;
+    }
+
+    let Some(skeleton) = build() else {
+        return;
+    };
+
+    let res = path.parent()
+        .map(std::fs::create_dir_all)
+        .unwrap_or(Ok(()))
+        .map_err(Error::from)
+        .and_then(|_| utils::write_json_file(&skeleton, &path));
+    match res {
+        Ok(_) => debug!("Wrote asset skeleton: {}", Path::new(DIR).join(rel_path).display()),
+        Err(e) => warn!("Failed to write asset skeleton '{}': {}", path.display(), e)
+    }
+}
@@ -0,0 +1,175 @@
+/*
+    Binary cache for the localized data dicts, parsing large JSON dicts is slow on low-end devices.
+    Cache files are stored in the data dir's dict_cache dir, outside of the localized data dirs so
+    that they don't end up in the translation repo's staging dir or backups. Each localized data dir
+    gets its own subdir, keyed by the hash of its path.
+    A cache file is used as-is if the source's size and mtime haven't changed, otherwise the source
+    is hashed and compared to the blake3 hash that the cache was built from.
+
+    File format: MAGIC, version (u32 LE), source size (u64 LE), source mtime (u64 LE, ns),
+    source hash (32 bytes), postcard encoded dict
+*/
+use std::{fs, path::{Path, PathBuf}, sync::atomic::{self, AtomicBool}, time::UNIX_EPOCH};
+
+use once_cell::sync::OnceCell;
+use serde::{de::DeserializeOwned, Serialize};
+
+const MAGIC: &[u8; 4] = b"HDC\0";
+// Bump this whenever a dict type or the file format changes
+const VERSION: u32 = 2;
+const STAMP_LEN: usize = 16;
+const HEADER_LEN: usize = MAGIC.len() + 4 + STAMP_LEN + blake3::OUT_LEN;
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+static CACHE_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Sets the dir that the cache files are stored in. Caching is disabled until this is called.
+pub fn init(cache_dir: PathBuf) {
+    _ = CACHE_DIR.set(cache_dir);
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, atomic::Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(atomic::Ordering::Relaxed) && CACHE_DIR.get().is_some()
+}
+
+pub fn get_path(ld_path: &Path, rel_path: &Path) -> Option<PathBuf> {
+    let dir_hash = blake3::hash(ld_path.to_string_lossy().as_bytes()).to_hex();
+    let name = rel_path.to_string_lossy().replace(['/', '\\'], "_");
+    Some(CACHE_DIR.get()?.join(&dir_hash[..16]).join(name + ".bin"))
+}
+
+/// Size and modification time of a source file.
+#[derive(PartialEq, Eq)]
+pub struct SourceStamp {
+    size: u64,
+    mtime: u64
+}
+
+impl SourceStamp {
+    pub fn get(path: &Path) -> Option<SourceStamp> {
+        let metadata = fs::metadata(path).ok()?;
+        let mtime = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(SourceStamp {
+            size: metadata.len(),
+            mtime: mtime.as_nanos() as u64
+        })
+    }
+
+    fn to_bytes(&self) -> [u8; STAMP_LEN] {
+        let mut bytes = [0; STAMP_LEN];
+        bytes[..8].copy_from_slice(&self.size.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.mtime.to_le_bytes());
+        bytes
+    }
+}
+
+struct CacheFile {
+    data: Vec<u8>
+}
+
+impl CacheFile {
+    fn read(cache_path: &Path) -> Option<CacheFile> {
+        let data = fs::read(cache_path).ok()?;
+        if data.len() < HEADER_LEN || &data[..MAGIC.len()] != MAGIC || data[MAGIC.len()..MAGIC.len() + 4] != VERSION.to_le_bytes() {
+            return None;
+        }
+        Some(CacheFile { data })
+    }
+
+    fn stamp(&self) -> &[u8] {
+        let start = MAGIC.len() + 4;
+        &self.data[start..start + STAMP_LEN]
+    }
+
+    fn hash(&self) -> &[u8] {
+        &self.data[HEADER_LEN - blake3::OUT_LEN..HEADER_LEN]
+    }
+
+    fn decode<T: DeserializeOwned>(&self, cache_path: &Path) -> Option<T> {
+        match postcard::from_bytes(&self.data[HEADER_LEN..]) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                warn!("Failed to load dict cache '{}': {}", cache_path.display(), e);
+                None
+            }
+        }
+    }
+}
+
+/// Returns None if the cache doesn't exist or if the source's size or mtime has changed.
+pub fn load_if_unchanged<T: DeserializeOwned>(cache_path: &Path, stamp: &SourceStamp) -> Option<T> {
+    let file = CacheFile::read(cache_path)?;
+    if file.stamp() != stamp.to_bytes() {
+        return None;
+    }
+    file.decode(cache_path)
+}
+
+/// Returns None if the cache doesn't exist or is outdated.
+pub fn load<T: DeserializeOwned>(cache_path: &Path, source_hash: &blake3::Hash) -> Option<T> {
+    let file = CacheFile::read(cache_path)?;
+    if file.hash() != source_hash.as_bytes() {
+        return None;
+    }
+    file.decode(cache_path)
+}
+
+pub fn save<T: Serialize>(cache_path: &Path, stamp: Option<&SourceStamp>, source_hash: &blake3::Hash, dict: &T) {
+    let payload = match postcard::to_stdvec(dict) {
+        Ok(v) => v,
+        Err(e) => {
+            warn!("Failed to serialize dict cache '{}': {}", cache_path.display(), e);
+            return;
+        }
+    };
+
+    let mut data = Vec::with_capacity(HEADER_LEN + payload.len());
+    data.extend_from_slice(MAGIC);
+    data.extend_from_slice(&VERSION.to_le_bytes());
+    // A zeroed stamp never matches, the source will be hashed next time
+    data.extend_from_slice(&stamp.map(SourceStamp::to_bytes).unwrap_or_default());
+    data.extend_from_slice(source_hash.as_bytes());
+    data.extend_from_slice(&payload);
+
+    let res = cache_path.parent()
+        .map(fs::create_dir_all)
+        .unwrap_or(Ok(()))
+        .and_then(|_| fs::write(cache_path, data));
+    if let Err(e) = res {
+        // Not fatal, the dict has been loaded already
+        warn!("Failed to write dict cache '{}': {}", cache_path.display(), e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let dir = std::env::temp_dir().join(format!("hachimi_dict_cache_test_{}", std::process::id()));
+        let ld_path = dir.join("localized_data");
+        let source = ld_path.join("dict.json");
+        fs::create_dir_all(&ld_path).unwrap();
+        fs::write(&source, "{}").unwrap();
+        init(dir.join("dict_cache"));
+
+        let cache_path = get_path(&ld_path, Path::new("dict.json")).unwrap();
+        assert!(!cache_path.starts_with(&ld_path));
+        let hash = blake3::hash(b"{}");
+        let stamp = SourceStamp::get(&source).unwrap();
+        save(&cache_path, Some(&stamp), &hash, &vec![1u32, 2, 3]);
+
+        assert_eq!(load_if_unchanged::<Vec<u32>>(&cache_path, &stamp), Some(vec![1, 2, 3]));
+        let touched = SourceStamp { mtime: stamp.mtime + 1, ..stamp };
+        assert_eq!(load_if_unchanged::<Vec<u32>>(&cache_path, &touched), None);
+        assert_eq!(load::<Vec<u32>>(&cache_path, &hash), Some(vec![1, 2, 3]));
+        assert_eq!(load::<Vec<u32>>(&cache_path, &blake3::hash(b"[]")), None);
+
+        _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,6 +1,7 @@
 use std::fmt;
 
 #[derive(Debug)]
+#[allow(clippy::enum_variant_names)]
 pub enum Error {
     SymbolNotFound(String, String),
     HookingError(String),
@@ -10,10 +11,14 @@ pub enum Error {
     IoError(std::io::Error),
     JsonParseError(serde_json::Error),
     GuiRendererInitError(String),
-    HttpError(ureq::Error),
+    // Boxed since it's much bigger than the other variants
+    HttpError(Box<ureq::Error>),
     PluralParsing,
+    UnknownPluralLocale(String),
+    PoParseError(usize, String),
     OutOfDiskSpace,
     FileHashMismatch(String),
+    InvalidSignature(String),
     ZipError(zip::result::ZipError),
     RuntimeError(String)
 }
@@ -48,6 +53,12 @@ impl fmt::Display for Error {
             Error::PluralParsing => {
                 write!(f, "Failed to parse plural expression")
             }
+            Error::UnknownPluralLocale(locale) => {
+                write!(f, "Unknown CLDR plural locale: {}", locale)
+            }
+            Error::PoParseError(line, msg) => {
+                write!(f, "Failed to parse PO file: {} (at line {})", msg, line)
+            }
             Error::HttpError(error) => {
                 write!(f, "HTTP error: {}", error)
             }
@@ -57,6 +68,9 @@ impl fmt::Display for Error {
             Error::FileHashMismatch(name) => {
                 write!(f, "File hash mismatch: {}", name)
             }
+            Error::InvalidSignature(reason) => {
+                write!(f, "Invalid signature: {}", reason)
+            }
             Error::ZipError(error) => {
                 write!(f, "Zip error: {}", error)
             },
@@ -81,7 +95,7 @@ impl From<serde_json::Error> for Error {
 
 impl From<ureq::Error> for Error {
     fn from(e: ureq::Error) -> Self {
-        Error::HttpError(e)
+        Error::HttpError(Box::new(e))
     }
 }
 
@@ -0,0 +1,94 @@
+/*
+    Machine translation glossary: { "source term": "translation" }
+    Terms are replaced with placeholders ({{0}}, {{1}}...) before the text is sent to the MT backend,
+    and the placeholders are replaced with the terms' translations afterwards, so that names come out
+    the same way every time. Longer terms take priority over shorter ones.
+*/
+use fnv::FnvHashMap;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+// Backends sometimes add spaces inside of the braces
+static PLACEHOLDER_REGEX: Lazy<Regex> = Lazy::new(|| Regex::new(r"\{\{\s*(\d+)\s*\}\}").unwrap());
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(transparent)]
+pub struct Glossary {
+    terms: FnvHashMap<String, String>,
+    #[serde(skip)]
+    regex: Option<Regex>
+}
+
+impl Glossary {
+    /// Must be called after the glossary has been fully loaded/merged.
+    pub fn compile(&mut self) {
+        let mut terms: Vec<&String> = self.terms.keys().filter(|term| !term.is_empty()).collect();
+        if terms.is_empty() {
+            self.regex = None;
+            return;
+        }
+
+        // Alternations match the first term that fits, so the longest ones go first
+        terms.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+        let pattern = terms.iter().map(|term| regex::escape(term)).collect::<Vec<_>>().join("|");
+        match Regex::new(&pattern) {
+            Ok(regex) => self.regex = Some(regex),
+            Err(e) => error!("Failed to compile glossary: {}", e)
+        }
+    }
+
+    pub fn merge(&mut self, other: Glossary) {
+        self.terms.extend(other.terms);
+        self.regex = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.terms.len()
+    }
+
+    pub fn get(&self, term: &str) -> Option<&str> {
+        self.terms.get(term).map(|s| s.as_str())
+    }
+
+    pub fn terms(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.terms.iter()
+    }
+
+    /// Replaces the terms in the text with placeholders.
+    /// Returns the new text and the translations that the placeholders stand for.
+    pub fn protect<'a>(&'a self, text: &str) -> (String, Vec<&'a str>) {
+        let Some(regex) = &self.regex else {
+            return (text.to_owned(), Vec::new());
+        };
+
+        let mut replacements: Vec<&str> = Vec::new();
+        let protected = regex.replace_all(text, |caps: &Captures| {
+            let translation = self.get(&caps[0]).unwrap_or_default();
+            let i = replacements.iter().position(|r| *r == translation).unwrap_or_else(|| {
+                replacements.push(translation);
+                replacements.len() - 1
+            });
+            format!("{{{{{}}}}}", i)
+        });
+        (protected.into_owned(), replacements)
+    }
+
+    /// Puts the translations back in place of the placeholders.
+    pub fn restore(text: &str, replacements: &[&str]) -> String {
+        if replacements.is_empty() {
+            return text.to_owned();
+        }
+
+        PLACEHOLDER_REGEX.replace_all(text, |caps: &Captures| {
+            caps[1].parse::<usize>().ok()
+                .and_then(|i| replacements.get(i))
+                .map(|s| s.to_string())
+                .unwrap_or_else(|| caps[0].to_owned())
+        }).into_owned()
+    }
+}
@@ -18,7 +18,7 @@ use crate::il2cpp::hook::umamusume::WebViewManager;
 #[cfg(target_os = "windows")]
 use crate::il2cpp::hook::UnityEngine_CoreModule::QualitySettings;
 
-use super::{hachimi::{self, Language}, http::AsyncRequest, tl_repo::{self, RepoInfo}, utils, Hachimi};
+use super::{hachimi::{self, Language}, http::AsyncRequest, mt, po, source_hash, tl_repo::{self, RepoInfo}, untranslated, utils, Hachimi};
 
 macro_rules! add_font {
     ($fonts:expr, $family_fonts:expr, $filename:literal) => {
@@ -350,6 +350,32 @@ impl Gui {
                     if ui.button(t!("menu.check_for_updates_pedantic")).clicked() {
                         hachimi.tl_updater.clone().check_for_updates(true);
                     }
+                    if hachimi.tl_updater.has_failed_files() && ui.button(t!("menu.retry_failed_tl_files")).clicked() {
+                        hachimi.tl_updater.clone().retry_failed();
+                    }
+                    if ui.button(t!("menu.sideload_tl_repo")).clicked() {
+                        show_window = Some(Box::new(SideloadRepoWindow::new()));
+                    }
+                    if hachimi.tl_updater.has_backup() && ui.button(t!("menu.rollback_tl_update")).clicked() {
+                        show_window = Some(Box::new(SimpleYesNoDialog::new(
+                            &t!("tl_rollback_dialog.title"),
+                            &t!("tl_rollback_dialog.content"),
+                            |ok| {
+                                if !ok { return; }
+                                Hachimi::instance().tl_updater.clone().rollback();
+                            }
+                        )));
+                    }
+                    let config = hachimi.config.load();
+                    if config.auto_translate_localize || config.auto_translate_master_text {
+                        let status = mt::queue::status();
+                        ui.label(t!("menu.auto_translate_status",
+                            pending = status.pending, translated = status.translated, failed = status.failed
+                        ));
+                        if let Some(e) = &status.last_error {
+                            ui.label(t!("menu.auto_translate_last_error", error = e));
+                        }
+                    }
                     if hachimi.config.load().translator_mode {
                         if ui.button(t!("menu.dump_localize_dict")).clicked() {
                             Thread::main_thread().schedule(|| {
@@ -364,6 +390,38 @@ impl Gui {
                                 }
                             })
                         }
+                        if ui.button(t!("menu.export_po")).clicked() {
+                            Thread::main_thread().schedule(|| {
+                                let hachimi = Hachimi::instance();
+                                let strings = Localize::dump_strings();
+                                let out_dir = hachimi.get_data_path(po::EXPORT_DIR);
+                                let res = po::export_dicts(&hachimi.localized_data.load(), &strings, &out_dir);
+                                let mut gui = Gui::instance().unwrap().lock().unwrap();
+                                match res {
+                                    Ok(count) => gui.show_notification(&t!("notification.exported_po", count = count, dirname = po::EXPORT_DIR)),
+                                    Err(e) => gui.show_notification(&e.to_string())
+                                }
+                            })
+                        }
+                        if ui.button(t!("menu.save_untranslated_report", count = untranslated::count())).clicked() {
+                            show_notification = Some(match untranslated::save_report() {
+                                Ok(_) => t!("notification.saved_untranslated_report", filename = untranslated::REPORT_FILENAME),
+                                Err(e) => e.to_string().into()
+                            });
+                        }
+                        if ui.button(t!("menu.save_stale_report", count = source_hash::count())).clicked() {
+                            show_notification = Some(match source_hash::save_report() {
+                                Ok(_) => t!("notification.saved_stale_report", filename = source_hash::REPORT_FILENAME),
+                                Err(e) => e.to_string().into()
+                            });
+                        }
+                        if ui.button(t!("menu.export_mt_cache", count = mt::cache::count())).clicked() {
+                            let out_dir = hachimi.get_data_path(mt::cache::EXPORT_DIR);
+                            show_notification = Some(match mt::cache::export(&hachimi.config.load().mt, &out_dir) {
+                                Ok(count) => t!("notification.exported_mt_cache", count = count, dirname = mt::cache::EXPORT_DIR),
+                                Err(e) => e.to_string().into()
+                            });
+                        }
                     }
                     ui.separator();
 
@@ -523,10 +581,6 @@ impl Gui {
                     .desired_height(4.0)
                     .desired_width(140.0)
                 );
-                ui.label(
-                    egui::RichText::new(t!("tl_updater.warning"))
-                    .font(egui::FontId::proportional(10.0))
-                );
             });
         });
     }
@@ -958,6 +1012,14 @@ impl ConfigEditor {
                 ui.checkbox(&mut config.disable_translations, "");
                 ui.end_row();
 
+                ui.label(t!("config_editor.auto_reload_localized_data"));
+                ui.checkbox(&mut config.auto_reload_localized_data, "");
+                ui.end_row();
+
+                ui.label(t!("config_editor.apply_stale_translations"));
+                ui.checkbox(&mut config.apply_stale_translations, "");
+                ui.end_row();
+
                 ui.label(t!("config_editor.enable_ipc"));
                 ui.checkbox(&mut config.enable_ipc, "");
                 ui.end_row();
@@ -973,6 +1035,14 @@ impl ConfigEditor {
                 ui.label(t!("config_editor.auto_translate_ui"));
                 ui.checkbox(&mut config.auto_translate_localize, "");
                 ui.end_row();
+
+                ui.label(t!("config_editor.auto_translate_master_text"));
+                ui.checkbox(&mut config.auto_translate_master_text, "");
+                ui.end_row();
+
+                ui.label(t!("config_editor.mt_backend"));
+                Gui::run_combo(ui, "mt_backend", &mut config.mt.backend, mt::MtBackend::CHOICES);
+                ui.end_row();
             },
 
             ConfigEditorTab::Graphics => {
@@ -1173,7 +1243,8 @@ struct FirstTimeSetupWindow {
     id: egui::Id,
     index_request: Arc<AsyncRequest<Vec<RepoInfo>>>,
     current_page: usize,
-    current_tl_repo: usize
+    current_tl_repo: usize,
+    selected_addons: Vec<usize>
 }
 
 impl FirstTimeSetupWindow {
@@ -1182,7 +1253,8 @@ impl FirstTimeSetupWindow {
             id: random_id(),
             index_request: Arc::new(tl_repo::new_meta_index_request()),
             current_page: 0,
-            current_tl_repo: 0
+            current_tl_repo: 0,
+            selected_addons: Vec::new()
         }
     }
 }
@@ -1225,17 +1297,37 @@ impl Window for FirstTimeSetupWindow {
 
                         let mut selected = false;
                         async_request_ui_content(ui, self.index_request.clone(), |ui, repo_list| {
-                            selected = repo_list.get(self.current_tl_repo).is_some();
+                            selected = repo_list.get(self.current_tl_repo).is_some_and(|repo| !repo.addon);
                             egui::ScrollArea::vertical().show(ui, |ui| {
                                 egui::Frame::none()
                                 .inner_margin(egui::Margin::symmetric(8.0, 0.0))
                                 .show(ui, |ui| {
-                                    for (i, repo) in repo_list.iter().enumerate() {
+                                    for (i, repo) in repo_list.iter().enumerate().filter(|(_, repo)| !repo.addon) {
                                         ui.radio_value(&mut self.current_tl_repo, i, &repo.name);
                                         if let Some(short_desc) = &repo.short_desc {
                                             ui.label(egui::RichText::new(short_desc).small());
                                         }
                                     }
+
+                                    // Add-ons can be picked on top of the main repo
+                                    if repo_list.iter().any(|repo| repo.addon) {
+                                        ui.add_space(4.0);
+                                        ui.label(t!("first_time_setup.translation_repo_addons"));
+                                    }
+                                    for (i, repo) in repo_list.iter().enumerate().filter(|(_, repo)| repo.addon) {
+                                        let mut checked = self.selected_addons.contains(&i);
+                                        if ui.checkbox(&mut checked, &repo.name).changed() {
+                                            if checked {
+                                                self.selected_addons.push(i);
+                                            }
+                                            else {
+                                                self.selected_addons.retain(|addon| *addon != i);
+                                            }
+                                        }
+                                        if let Some(short_desc) = &repo.short_desc {
+                                            ui.label(egui::RichText::new(short_desc).small());
+                                        }
+                                    }
                                 });
                             });
                         });
@@ -1272,6 +1364,12 @@ impl Window for FirstTimeSetupWindow {
                 };
 
                 config.translation_repo_index = Some(repo.index.clone());
+                config.translation_repo_public_key = repo.public_key.clone();
+                config.translation_repo_index_mirrors = repo.index_mirrors.clone();
+                config.translation_repo_addons = self.selected_addons.iter()
+                    .filter_map(|i| repo_list.get(*i))
+                    .map(tl_repo::RepoSubscription::from_info)
+                    .collect();
             }
 
             save_and_reload_config(config);
@@ -1361,6 +1459,58 @@ impl Window for LicenseWindow {
     }
 }
 
+struct SideloadRepoWindow {
+    id: egui::Id,
+    path: String
+}
+
+impl SideloadRepoWindow {
+    fn new() -> SideloadRepoWindow {
+        SideloadRepoWindow {
+            id: random_id(),
+            path: String::new()
+        }
+    }
+}
+
+impl Window for SideloadRepoWindow {
+    fn run(&mut self, ctx: &egui::Context) -> bool {
+        let mut open = true;
+        let mut open2 = true;
+        let mut import = false;
+        let can_import = !self.path.trim().is_empty();
+
+        new_window(ctx, t!("sideload_tl_repo_dialog.title"))
+        .id(self.id)
+        .open(&mut open)
+        .show(ctx, |ui| {
+            simple_window_layout(ui, self.id,
+                |ui| {
+                    ui.label(t!("sideload_tl_repo_dialog.content"));
+                    ui.add(egui::TextEdit::singleline(&mut self.path).desired_width(f32::INFINITY));
+                },
+                |ui| {
+                    if ui.button(t!("cancel")).clicked() {
+                        open2 = false;
+                    }
+                    if ui.add_enabled(can_import, egui::Button::new(t!("sideload_tl_repo_dialog.import"))).clicked() {
+                        import = true;
+                        open2 = false;
+                    }
+                }
+            );
+        });
+
+        if import {
+            // Relative paths are relative to the data dir
+            let hachimi = Hachimi::instance();
+            hachimi.tl_updater.clone().sideload(hachimi.get_data_path(self.path.trim()));
+        }
+
+        open && open2
+    }
+}
+
 pub struct PersistentMessageWindow {
     id: egui::Id,
     title: String,
@@ -1,12 +1,13 @@
 use std::{fs, path::{Path, PathBuf}, process, sync::{atomic::{self, AtomicBool, AtomicI32}, Arc, Mutex}};
 use arc_swap::ArcSwap;
-use fnv::{FnvHashMap, FnvHashSet};
+use fnv::FnvHashSet;
 use once_cell::sync::OnceCell;
-use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde::{Deserialize, Serialize};
 
 use crate::{core::plugin_api::Plugin, gui_impl, hachimi_impl, il2cpp::{self, hook::umamusume::{CySpringController::SpringUpdateMode, GameSystem}}};
 
-use super::{game::Game, ipc, plurals, template, template_filters, tl_repo, utils, Error, Interceptor};
+use super::{dict_cache, game::Game, ipc, ld_watcher, mt, localized_data::default_serde_instance, template, template_filters, tl_repo, untranslated, utils, Error, Interceptor};
+pub use super::localized_data::{AssetInfo, AssetMetadata, LocalizedData, LocalizedDataConfig, OsOption, UITextConfig};
 
 pub struct Hachimi {
     // Hooking stuff
@@ -87,8 +88,10 @@ impl Hachimi {
     fn new() -> Result<Hachimi, Error> {
         let game = Game::init();
         let config = Self::load_config(&game.data_dir)?;
+        dict_cache::init(game.data_dir.join("dict_cache"));
 
         config.language.set_locale();
+        untranslated::set_enabled(config.translator_mode);
 
         Ok(Hachimi {
             interceptor: Interceptor::default(),
@@ -138,6 +141,8 @@ impl Hachimi {
         };
 
         new_config.language.set_locale();
+        untranslated::set_enabled(new_config.translator_mode);
+        mt::cache::flush_or_log();
         self.config.store(Arc::new(new_config));
     }
 
@@ -153,6 +158,11 @@ impl Hachimi {
         self.save_config(&config)?;
 
         config.language.set_locale();
+        untranslated::set_enabled(config.translator_mode);
+        mt::cache::flush_or_log();
+        if config.auto_reload_localized_data {
+            ld_watcher::start();
+        }
         self.config.store(Arc::new(config));
         Ok(())
     }
@@ -209,6 +219,10 @@ impl Hachimi {
             ipc::start_http(config.ipc_listen_all);
         }
 
+        if config.auto_reload_localized_data {
+            ld_watcher::start();
+        }
+
         hachimi_impl::on_hooking_finished(self);
 
         for plugin in self.plugins.lock().unwrap().iter() {
@@ -241,12 +255,6 @@ impl Hachimi {
     }
 }
 
-fn default_serde_instance<'a, T: Deserialize<'a>>() -> Option<T> {
-    let empty_data = std::iter::empty::<((), ())>();
-    let empty_deserializer = serde::de::value::MapDeserializer::<_, serde::de::value::Error>::new(empty_data);
-    T::deserialize(empty_deserializer).ok()
-}
-
 #[derive(Deserialize, Serialize, Clone)]
 pub struct Config {
     #[serde(default)]
@@ -258,12 +266,27 @@ pub struct Config {
     #[serde(default)]
     pub disable_gui_once: bool,
     pub localized_data_dir: Option<String>,
+    // Extra data dirs layered on top of localized_data_dir, using the same layout.
+    // Later entries take priority over earlier ones.
+    #[serde(default)]
+    pub localized_data_overlay_dirs: Vec<String>,
+    #[serde(default)]
+    pub auto_reload_localized_data: bool,
     pub target_fps: Option<i32>,
     #[serde(default = "Config::default_open_browser_url")]
     pub open_browser_url: String,
     #[serde(default = "Config::default_virtual_res_mult")]
     pub virtual_res_mult: f32,
     pub translation_repo_index: Option<String>,
+    // ed25519 public key (base64) of the translation repo, the index must be signed with it if set.
+    // Pinned from the meta index if unset, clear it to re-pin a key that was rotated or pinned by mistake
+    pub translation_repo_public_key: Option<String>,
+    // Tried in order if translation_repo_index can't be reached
+    #[serde(default)]
+    pub translation_repo_index_mirrors: Vec<String>,
+    // Extra repos layered on top of the main one, in their own dirs
+    #[serde(default)]
+    pub translation_repo_addons: Vec<tl_repo::RepoSubscription>,
     #[serde(default)]
     pub skip_first_time_setup: bool,
     #[serde(default)]
@@ -286,17 +309,32 @@ pub struct Config {
     pub force_allow_dynamic_camera: bool,
     #[serde(default)]
     pub live_theater_allow_same_chara: bool,
+    // Legacy, use mt.url instead
     pub sugoi_url: Option<String>,
+    // Machine translation backend used for auto translation
+    #[serde(default)]
+    pub mt: mt::MtConfig,
     #[serde(default)]
     pub auto_translate_stories: bool,
     #[serde(default)]
     pub auto_translate_localize: bool,
+    // text_data, character_system_text and race jikkyo text from the SQL hooks
+    #[serde(default)]
+    pub auto_translate_master_text: bool,
+    // Apply asset translations even if their source_hash doesn't match
+    #[serde(default)]
+    pub apply_stale_translations: bool,
     #[serde(default)]
     pub disable_skill_name_translation: bool,
     #[serde(default)]
     pub language: Language,
     #[serde(default = "Config::default_meta_index_url")]
     pub meta_index_url: String,
+    // Tried in order if meta_index_url can't be reached
+    #[serde(default)]
+    pub meta_index_mirrors: Vec<String>,
+    // Used for every request Hachimi makes, e.g. "http://127.0.0.1:8080"
+    pub http_proxy: Option<String>,
     pub physics_update_mode: Option<SpringUpdateMode>,
     #[serde(default = "Config::default_ui_animation_scale")]
     pub ui_animation_scale: f32,
@@ -328,25 +366,6 @@ impl Default for Config {
     }
 }
 
-#[derive(Deserialize, Default, Clone)]
-pub struct OsOption<T> {
-    #[cfg(target_os = "android")]
-    android: Option<T>,
-
-    #[cfg(target_os = "windows")]
-    windows: Option<T>
-}
-
-impl<T> OsOption<T> {
-    pub fn as_ref(&self) -> Option<&T> {
-        #[cfg(target_os = "android")]
-        return self.android.as_ref();
-
-        #[cfg(target_os = "windows")]
-        return self.windows.as_ref();
-    }
-}
-
 #[derive(Default, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
 #[allow(non_camel_case_types)]
 pub enum Language {
@@ -398,260 +417,36 @@ impl Language {
     }
 }
 
-#[derive(Default)]
-pub struct LocalizedData {
-    pub config: LocalizedDataConfig,
-    path: Option<PathBuf>,
-
-    pub localize_dict: FnvHashMap<String, String>,
-    pub hashed_dict: FnvHashMap<u64, String>,
-    pub text_data_dict: FnvHashMap<i32, FnvHashMap<i32, String>>, // {"category": {"index": "text"}}
-    pub character_system_text_dict: FnvHashMap<i32, FnvHashMap<i32, String>>, // {"character_id": {"voice_id": "text"}}
-    pub race_jikkyo_comment_dict: FnvHashMap<i32, String>, // {"id": "text"}
-    pub race_jikkyo_message_dict: FnvHashMap<i32, String>, // {"id": "text"}
-    assets_path: Option<PathBuf>,
-
-    pub plural_form: plurals::Resolver,
-    pub ordinal_form: plurals::Resolver
-}
-
 impl LocalizedData {
-    fn new(config: &Config, data_dir: &Path) -> Result<LocalizedData, Error> {
-        if config.disable_translations {
+    fn new(hachimi_config: &Config, data_dir: &Path) -> Result<LocalizedData, Error> {
+        if hachimi_config.disable_translations {
             return Ok(LocalizedData::default());
         }
 
-        let path: Option<PathBuf>;
-        let config: LocalizedDataConfig = if let Some(ld_dir) = &config.localized_data_dir {
-            let ld_path = Path::new(data_dir).join(ld_dir);
-
-            // Create .nomedia
-            #[cfg(target_os = "android")]
-            { _ = fs::OpenOptions::new().create_new(true).write(true).open(ld_path.join(".nomedia")); }
-
-            let ld_config_path = ld_path.join("config.json");
-            path = Some(ld_path);
-
-            if fs::metadata(&ld_config_path).is_ok() {
-                let json = fs::read_to_string(&ld_config_path)?;
-                serde_json::from_str(&json)?
-            }
-            else {
-                warn!("Localized data config not found");
-                LocalizedDataConfig::default()
-            }
-        }
-        else {
-            path = None;
-            LocalizedDataConfig::default()
-        };
-
-        let plural_form = Self::parse_plural_form_or_default(&config.plural_form)?;
-        let ordinal_form = Self::parse_plural_form_or_default(&config.ordinal_form)?;
-
-        Ok(LocalizedData {
-            localize_dict: Self::load_dict_static(&path, config.localize_dict.as_ref()).unwrap_or_default(),
-            hashed_dict: Self::load_dict_static(&path, config.hashed_dict.as_ref()).unwrap_or_default(),
-            text_data_dict: Self::load_dict_static(&path, config.text_data_dict.as_ref()).unwrap_or_default(),
-            character_system_text_dict: Self::load_dict_static(&path, config.character_system_text_dict.as_ref()).unwrap_or_default(),
-            race_jikkyo_comment_dict: Self::load_dict_static(&path, config.race_jikkyo_comment_dict.as_ref()).unwrap_or_default(),
-            race_jikkyo_message_dict: Self::load_dict_static(&path, config.race_jikkyo_message_dict.as_ref()).unwrap_or_default(),
-            assets_path: path.as_ref()
-                .map(|p| config.assets_dir.as_ref()
-                    .map(|dir| p.join(dir))
-                )
-                .unwrap_or_default(),
-
-            plural_form,
-            ordinal_form,
-
-            config,
-            path
-        })
-    }
-
-    fn load_dict_static_ex<T: DeserializeOwned, P: AsRef<Path>>(ld_path_opt: &Option<PathBuf>, rel_path_opt: Option<P>, silent_fs_error: bool) -> Option<T> {
-        let Some(ld_path) = ld_path_opt else {
-            return None;
-        };
-        let Some(rel_path) = rel_path_opt else {
-            return None;
+        let Some(ld_dir) = &hachimi_config.localized_data_dir else {
+            return LocalizedData::load(None, Vec::new());
         };
+        let ld_path = data_dir.join(ld_dir);
 
-        let path = ld_path.join(rel_path);
-        let json = match fs::read_to_string(&path) {
-            Ok(v) => v,
-            Err(e) => {
-                if !silent_fs_error {
-                    error!("Failed to read '{}': {}", path.display(), e);
-                }
-                return None;
-            }
-        };
-
-        let dict = match serde_json::from_str::<T>(&json) {
-            Ok(v) => v,
-            Err(e) => {
-                error!("Failed to parse '{}': {}", path.display(), e);
-                return None;
-            }
-        };
-
-        Some(dict)
-    }
-
-    fn load_dict_static<T: DeserializeOwned, P: AsRef<Path>>(ld_path_opt: &Option<PathBuf>, rel_path_opt: Option<P>) -> Option<T> {
-        Self::load_dict_static_ex(ld_path_opt, rel_path_opt, false)
-    }
-
-    pub fn load_dict<T: DeserializeOwned, P: AsRef<Path>>(&self, rel_path_opt: Option<P>) -> Option<T> {
-        Self::load_dict_static(&self.path, rel_path_opt)
-    }
-
-    pub fn load_assets_dict<T: DeserializeOwned, P: AsRef<Path>>(&self, rel_path_opt: Option<P>) -> Option<T> {
-        Self::load_dict_static_ex(&self.assets_path, rel_path_opt, true)
-    }
-
-    fn parse_plural_form_or_default(opt: &Option<String>) -> Result<plurals::Resolver, Error> {
-        if let Some(plural_form) = opt {
-            Ok(plurals::Resolver::Expr(plurals::Ast::parse(plural_form)?))
-        }
-        else {
-            Ok(plurals::Resolver::Function(|_| 0))
-        }
-    }
-
-    pub fn get_assets_path<P: AsRef<Path>>(&self, rel_path: P) -> Option<PathBuf> {
-        self.assets_path.as_ref().map(|p| p.join(rel_path))
-    }
-
-    pub fn get_data_path<P: AsRef<Path>>(&self, rel_path: P) -> Option<PathBuf> {
-        self.path.as_ref().map(|p| p.join(rel_path))
-    }
-
-    pub fn load_asset_metadata<P: AsRef<Path>>(&self, rel_path: P) -> AssetMetadata {
-        let mut path = rel_path.as_ref().to_owned();
-        path.set_extension("json");
-        self.load_assets_dict(Some(path)).unwrap_or_else(|| AssetInfo::<()>::default()).metadata()
-    }
-
-    pub fn load_asset_info<P: AsRef<Path>, T: DeserializeOwned>(&self, rel_path: P) -> AssetInfo<T> {
-        let mut path = rel_path.as_ref().to_owned();
-        path.set_extension("json");
-        self.load_assets_dict(Some(path)).unwrap_or_else(|| AssetInfo::default())
-    }
-}
-
-#[derive(Deserialize, Clone)]
-pub struct LocalizedDataConfig {
-    pub localize_dict: Option<String>,
-    pub hashed_dict: Option<String>,
-    pub text_data_dict: Option<String>,
-    pub character_system_text_dict: Option<String>,
-    pub race_jikkyo_comment_dict: Option<String>,
-    pub race_jikkyo_message_dict: Option<String>,
-    pub assets_dir: Option<String>,
-    #[serde(default)]
-    pub extra_asset_bundle: OsOption<String>,
-    pub replacement_font_name: Option<String>,
-
-    pub plural_form: Option<String>,
-    pub ordinal_form: Option<String>,
-    #[serde(default)]
-    pub ordinal_types: Vec<String>,
-    #[serde(default)]
-    pub months: Vec<String>,
-    pub month_text_format: Option<String>,
-
-    #[serde(default)]
-    pub use_text_wrapper: bool,
-    // Predefined line widths are counts of cjk characters.
-    // 1 cjk char = 2 columns, so setting this value to 2 replicates the default behaviour.
-    pub line_width_multiplier: Option<f32>,
-
-    #[serde(default)]
-    pub auto_adjust_story_clip_length: bool,
-    pub story_line_count_offset: Option<i32>,
-    pub text_frame_line_spacing_multiplier: Option<f32>,
-    pub text_frame_font_size_multiplier: Option<f32>,
-    pub skill_list_item_desc_font_size_multiplier: Option<f32>,
-    #[serde(default)]
-    pub text_common_allow_overflow: bool,
-    #[serde(default)]
-    pub now_loading_comic_title_ellipsis: bool,
-
-    #[serde(default)]
-    pub remove_ruby: bool,
-    pub character_note_top_gallery_button: Option<UITextConfig>,
-    pub character_note_top_talk_gallery_button: Option<UITextConfig>,
-
-    pub news_url: Option<String>,
-
-    // RESERVED
-    #[serde(default)]
-    pub _debug: i32
-}
-
-#[derive(Deserialize, Clone)]
-pub struct UITextConfig {
-    pub text: Option<String>,
-    pub font_size: Option<i32>,
-    pub line_spacing: Option<f32>
-}
-
-impl Default for LocalizedDataConfig {
-    fn default() -> Self {
-        default_serde_instance().expect("default instance")
-    }
-}
-
-#[derive(Deserialize)]
-pub struct AssetInfo<T> {
-    #[cfg(target_os = "android")]
-    #[serde(default)]
-    android: AssetMetadata,
-
-    #[cfg(target_os = "windows")]
-    #[serde(default)]
-    windows: AssetMetadata,
-
-    pub data: Option<T>
-}
-
-// Can't derive(Default), see rust-lang/rust#26925
-impl<T> Default for AssetInfo<T> {
-    fn default() -> Self {
-        Self {
-            #[cfg(target_os = "android")]
-            android: Default::default(),
-
-            #[cfg(target_os = "windows")]
-            windows: Default::default(),
-
-            data: None
-        }
-    }
-}
-
-impl<T> AssetInfo<T> {
-    pub fn metadata(self) -> AssetMetadata {
+        // Create .nomedia
         #[cfg(target_os = "android")]
-        return self.android;
-
-        #[cfg(target_os = "windows")]
-        return self.windows;
-    }
-
-    pub fn metadata_ref(&self) -> &AssetMetadata {
-        #[cfg(target_os = "android")]
-        return &self.android;
+        { _ = fs::OpenOptions::new().create_new(true).write(true).open(ld_path.join(".nomedia")); }
+
+        // Overlays are only meaningful on top of a base dir
+        // Add-on repos go below the manual overlays
+        let overlay_paths: Vec<PathBuf> = tl_repo::addon_dirs(hachimi_config).iter()
+            .chain(hachimi_config.localized_data_overlay_dirs.iter())
+            .rev()
+            .map(|dir| data_dir.join(dir))
+            .filter(|p| {
+                let exists = p.is_dir();
+                if !exists {
+                    warn!("Localized data overlay not found: {}", p.display());
+                }
+                exists
+            })
+            .collect();
 
-        #[cfg(target_os = "windows")]
-        return &self.windows;
+        LocalizedData::load(Some(ld_path), overlay_paths)
     }
 }
-
-#[derive(Deserialize, Clone, Default)]
-pub struct AssetMetadata {
-    pub bundle_name: Option<String>
-}
\ No newline at end of file
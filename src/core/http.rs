@@ -1,21 +1,87 @@
-use std::{io::Write, sync::{atomic::{self, AtomicBool}, Arc}};
+use std::{fs, io::{Read, Write}, path::Path, sync::{atomic::{self, AtomicBool}, Arc}, time::Duration};
 
 use arc_swap::ArcSwap;
 use serde::de::DeserializeOwned;
 
-use super::Error;
+use super::{Error, Hachimi};
+
+/// Creates an agent with the user's HTTP settings (proxy).
+pub fn agent() -> ureq::Agent {
+    let mut builder = ureq::AgentBuilder::new();
+    if let Some(proxy_url) = Hachimi::instance().config.load().http_proxy.as_ref().filter(|s| !s.is_empty()) {
+        match ureq::Proxy::new(proxy_url) {
+            Ok(proxy) => builder = builder.proxy(proxy),
+            Err(e) => error!("Invalid HTTP proxy '{}': {}", proxy_url, e)
+        }
+    }
+    builder.build()
+}
+
+/// Like `agent`, but requests to a local server (e.g. a self-hosted translation server) skip the proxy.
+pub fn agent_for(url: &str) -> ureq::Agent {
+    if is_loopback(url) {
+        return ureq::Agent::new();
+    }
+    agent()
+}
+
+fn is_loopback(url: &str) -> bool {
+    let Ok(url) = url::Url::parse(url) else {
+        return false;
+    };
+    match url.host() {
+        Some(url::Host::Domain(domain)) => domain.eq_ignore_ascii_case("localhost"),
+        Some(url::Host::Ipv4(ip)) => ip.is_loopback(),
+        Some(url::Host::Ipv6(ip)) => ip.is_loopback(),
+        None => false
+    }
+}
+
+// Connection errors and server errors, another mirror might still work
+fn should_failover(e: &Error) -> bool {
+    match e {
+        Error::HttpError(e) => match **e {
+            ureq::Error::Status(code, _) => code >= 500,
+            ureq::Error::Transport(_) => true
+        },
+        Error::IoError(_) => true,
+        _ => false
+    }
+}
+
+/// Calls `f` with each of the urls in order until one of them succeeds, or fails with an error
+/// that another mirror wouldn't fix (anything other than connection errors and 5xx responses).
+pub fn with_failover<T>(urls: &[String], mut f: impl FnMut(&str) -> Result<T, Error>) -> Result<T, Error> {
+    let mut urls = urls.iter();
+    let Some(mut url) = urls.next() else {
+        return Err(Error::RuntimeError("No URLs to request".to_owned()));
+    };
+    loop {
+        match f(url) {
+            Err(e) if should_failover(&e) => {
+                let Some(next_url) = urls.next() else {
+                    return Err(e);
+                };
+                warn!("Request to '{}' failed ({}), trying '{}'", url, e, next_url);
+                url = next_url;
+            }
+            res => return res
+        }
+    }
+}
 
 pub struct AsyncRequest<T: Send + Sync> {
-    request: ureq::Request,
+    // Mirrors of the same endpoint, tried in order
+    urls: Vec<String>,
     map_fn: fn(ureq::Response) -> Result<T, Error>,
     running: AtomicBool,
     pub result: ArcSwap<Option<Result<T, Error>>>
 }
 
 impl<T: Send + Sync + 'static> AsyncRequest<T> {
-    pub fn new(request: ureq::Request, map_fn: fn(ureq::Response) -> Result<T, Error>) -> Self {
+    pub fn new(urls: Vec<String>, map_fn: fn(ureq::Response) -> Result<T, Error>) -> Self {
         AsyncRequest {
-            request,
+            urls,
             map_fn,
             running: AtomicBool::new(false),
             result: ArcSwap::default()
@@ -26,10 +92,8 @@ impl<T: Send + Sync + 'static> AsyncRequest<T> {
         self.result.store(Arc::new(None));
         self.running.store(true, atomic::Ordering::Release);
         std::thread::spawn(move || {
-            let res = match self.request.clone().call() {
-                Ok(v) => (self.map_fn)(v),
-                Err(e) => Err(Error::from(e))
-            };
+            let agent = agent();
+            let res = with_failover(&self.urls, |url| (self.map_fn)(agent.get(url).call()?));
             self.result.store(Arc::new(Some(res)));
             self.running.store(false, atomic::Ordering::Release);
         });
@@ -41,56 +105,392 @@ impl<T: Send + Sync + 'static> AsyncRequest<T> {
 }
 
 impl<T: Send + Sync + 'static + DeserializeOwned> AsyncRequest<T> {
-    pub fn with_json_response(request: ureq::Request) -> AsyncRequest<T> {
-        AsyncRequest::new(request, |res|
+    pub fn with_json_response(urls: Vec<String>) -> AsyncRequest<T> {
+        AsyncRequest::new(urls, |res|
             Ok(serde_json::from_str(&res.into_string()?)?)
         )
     }
 }
 
 pub fn get_json<T: DeserializeOwned>(url: &str) -> Result<T, Error> {
-    let res = ureq::get(url).call()?;
+    let res = agent().get(url).call()?;
     Ok(serde_json::from_str(&res.into_string()?)?)
 }
 
+pub fn read_bytes(res: ureq::Response) -> Result<Vec<u8>, Error> {
+    let mut data = Vec::new();
+    res.into_reader().read_to_end(&mut data)?;
+    Ok(data)
+}
+
 pub fn get_github_json<T: DeserializeOwned>(url: &str) -> Result<T, Error> {
-    let res = ureq::get(url)
+    let res = agent().get(url)
         .set("Accept", "application/vnd.github+json")
         .set("X-GitHub-Api-Version", "2022-11-28")
         .call()?;
     Ok(serde_json::from_str(&res.into_string()?)?)
 }
 
-pub fn download_file_buffered(res: ureq::Response, file: &mut std::fs::File, buffer: &mut [u8], mut add_bytes: impl FnMut(&[u8])) -> Result<(), Error> {
-    let mut reader = res.into_reader();
-    let mut buffer_pos = 0usize;
+const MAX_RETRIES: u32 = 5;
+// Doubled after every retry
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+enum DownloadError {
+    // Connection issues, the download can be continued
+    Interrupted(Error),
+    Fatal(Error)
+}
+
+impl From<std::io::Error> for DownloadError {
+    fn from(e: std::io::Error) -> Self {
+        DownloadError::Fatal(Error::from(e))
+    }
+}
+
+impl From<ureq::Error> for DownloadError {
+    fn from(e: ureq::Error) -> Self {
+        match e {
+            ureq::Error::Status(code, _) if code != 429 && code < 500 => DownloadError::Fatal(Error::from(e)),
+            _ => DownloadError::Interrupted(Error::from(e))
+        }
+    }
+}
+
+/// Downloads the file at `urls` (mirrors of the same file) to `path`, continuing from the file's current length
+/// if it already exists (the file is started over if the server doesn't support range requests).
+/// Interrupted downloads are continued on the next mirror, and once every mirror has been tried, from the first one
+/// again, up to MAX_RETRIES times with an increasing delay.
+/// `on_progress` receives the amount of bytes in the file so far and the total size, if it's known.
+pub fn download_file_resumable(
+    agent: &ureq::Agent, urls: &[String], path: &Path, buffer: &mut [u8], mut on_progress: impl FnMut(usize, Option<usize>)
+) -> Result<(), Error> {
+    if urls.is_empty() {
+        return Err(Error::RuntimeError("No URLs to download from".to_owned()));
+    }
+
+    let mut retries = 0;
+    let mut url_index = 0;
     loop {
-        let read_bytes = reader.read(&mut buffer[buffer_pos..])?;
+        let url = &urls[url_index];
+        match download_file_range(agent, url, path, buffer, &mut on_progress) {
+            Ok(_) => return Ok(()),
+            Err(DownloadError::Interrupted(e)) if url_index + 1 < urls.len() => {
+                url_index += 1;
+                warn!("Download of '{}' interrupted ({}), trying '{}'", url, e, urls[url_index]);
+            }
+            Err(DownloadError::Interrupted(e)) if retries < MAX_RETRIES => {
+                let delay = RETRY_DELAY * 2u32.pow(retries);
+                retries += 1;
+                url_index = 0;
+                warn!("Download of '{}' interrupted ({}), retrying in {}s", url, e, delay.as_secs());
+                std::thread::sleep(delay);
+            }
+            Err(DownloadError::Interrupted(e) | DownloadError::Fatal(e)) => return Err(e)
+        }
+    }
+}
+
+// Content-Range: bytes <start>-<end>/<size>
+fn content_range_start(res: &ureq::Response) -> Option<usize> {
+    let range = res.header("Content-Range")?.trim().strip_prefix("bytes ")?;
+    range.split('-').next()?.trim().parse().ok()
+}
 
-        let prev_buffer_pos = buffer_pos;
-        buffer_pos += read_bytes;
-        add_bytes(&buffer[prev_buffer_pos..buffer_pos]);
+fn download_file_range(
+    agent: &ureq::Agent, url: &str, path: &Path, buffer: &mut [u8], on_progress: &mut impl FnMut(usize, Option<usize>)
+) -> Result<(), DownloadError> {
+    let mut file = fs::File::options().create(true).append(true).open(path)?;
+    let mut downloaded = file.metadata()?.len() as usize;
 
-        if buffer_pos == buffer.len() {
-            buffer_pos = 0;
-            let written = file.write(&buffer)?;
-            if written != buffer.len() {
-                return Err(Error::OutOfDiskSpace);
+    let mut res = if downloaded > 0 {
+        match agent.get(url).set("Range", &format!("bytes={}-", downloaded)).call() {
+            // Nothing left to download, the file is either complete or not the right one at all;
+            // that's up to the caller's hash check to decide
+            Err(ureq::Error::Status(416, _)) => {
+                on_progress(downloaded, Some(downloaded));
+                return Ok(());
             }
+            res => res?
         }
+    }
+    else {
+        agent.get(url).call()?
+    };
 
+    if downloaded > 0 && (res.status() != 206 || content_range_start(&res) != Some(downloaded)) {
+        // Range not supported (we're getting the whole file), or not the range that we asked for
+        file.set_len(0)?;
+        downloaded = 0;
+        if res.status() == 206 {
+            res = agent.get(url).call()?;
+        }
+    }
+
+    let total = res.header("Content-Length")
+        .and_then(|s| s.parse::<usize>().ok())
+        .map(|len| downloaded + len);
+    on_progress(downloaded, total);
+
+    // Written as soon as it's read so that the file's length is always the resume point
+    let mut reader = res.into_reader();
+    loop {
+        let read_bytes = reader.read(buffer).map_err(|e| DownloadError::Interrupted(Error::from(e)))?;
         if read_bytes == 0 {
             break;
         }
+
+        file.write_all(&buffer[..read_bytes]).map_err(|e| match e.kind() {
+            std::io::ErrorKind::WriteZero => DownloadError::Fatal(Error::OutOfDiskSpace),
+            _ => DownloadError::from(e)
+        })?;
+        downloaded += read_bytes;
+        on_progress(downloaded, total);
     }
 
-    // Download finished, flush the buffer
-    if buffer_pos != 0 {
-        let written = file.write(&buffer[..buffer_pos])?;
-        if written != buffer_pos {
-            return Err(Error::OutOfDiskSpace);
+    if let Some(total) = total {
+        if downloaded < total {
+            return Err(DownloadError::Interrupted(Error::RuntimeError(
+                format!("Connection closed after {} of {} bytes", downloaded, total)
+            )));
         }
     }
 
+    file.sync_data()?;
     Ok(())
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const CONTENT_LEN: usize = 1000;
+
+    fn content() -> Vec<u8> {
+        (0..CONTENT_LEN).map(|i| (i % 251) as u8).collect()
+    }
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("hachimi_http_test_{}_{}", std::process::id(), name));
+        _ = fs::remove_file(&path);
+        path
+    }
+
+    fn download(url: String, path: &Path) -> Result<(), Error> {
+        let mut buffer = [0u8; 64];
+        download_file_resumable(&ureq::Agent::new(), &[url], path, &mut buffer, |_, _| {})
+    }
+
+    // Honors Range requests, but cuts the connection midway through full downloads
+    fn range_handler(request: &test_server::Request, stream: &mut std::net::TcpStream) {
+        let content = content();
+        let Some(start) = request.header("Range")
+            .and_then(|s| s.strip_prefix("bytes="))
+            .and_then(|s| s.trim_end_matches('-').parse::<usize>().ok())
+        else {
+            let head = format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", CONTENT_LEN);
+            _ = stream.write_all(head.as_bytes());
+            _ = stream.write_all(&content[..400]);
+            return;
+        };
+
+        if start >= CONTENT_LEN {
+            return test_server::respond(stream, "416 Range Not Satisfiable", &[], b"");
+        }
+        let content_range = format!("bytes {}-{}/{}", start, CONTENT_LEN - 1, CONTENT_LEN);
+        test_server::respond(stream, "206 Partial Content", &[("Content-Range", &content_range)], &content[start..]);
+    }
+
+    #[test]
+    fn resumes_interrupted_download() {
+        let (url, server) = test_server::spawn(2, range_handler);
+        let path = temp_path("resume");
+
+        download(url, &path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), content());
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests[0].header("Range"), None);
+        assert_eq!(requests[1].header("Range"), Some("bytes=400-"));
+        _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn range_not_satisfiable_keeps_file() {
+        let (url, server) = test_server::spawn(1, range_handler);
+        let path = temp_path("416");
+        fs::write(&path, content()).unwrap();
+
+        download(url, &path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), content());
+        assert_eq!(server.join().unwrap().len(), 1);
+        _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn wrong_content_range_restarts() {
+        let content = content();
+        let (url, server) = test_server::spawn(2, move |request, stream| {
+            if request.header("Range").is_some() {
+                // Not the requested range
+                let content_range = format!("bytes 0-{}/{}", CONTENT_LEN - 1, CONTENT_LEN);
+                test_server::respond(stream, "206 Partial Content", &[("Content-Range", &content_range)], &content);
+            }
+            else {
+                test_server::respond(stream, "200 OK", &[], &content);
+            }
+        });
+        let path = temp_path("content_range");
+        fs::write(&path, [0xFFu8; 300]).unwrap();
+
+        download(url, &path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), self::content());
+        assert_eq!(server.join().unwrap().len(), 2);
+        _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn range_not_supported_restarts() {
+        let content = content();
+        let (url, _) = test_server::spawn(1, move |_, stream| test_server::respond(stream, "200 OK", &[], &content));
+        let path = temp_path("no_range");
+        fs::write(&path, [0xFFu8; 300]).unwrap();
+
+        download(url, &path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), self::content());
+        _ = fs::remove_file(&path);
+    }
+
+    // Returns the urls that were requested along with the result
+    fn get_with_failover(urls: &[String]) -> (Vec<String>, Result<String, Error>) {
+        let mut requested = Vec::new();
+        let res = with_failover(urls, |url| {
+            requested.push(url.to_owned());
+            Ok(ureq::Agent::new().get(url).call()?.into_string()?)
+        });
+        (requested, res)
+    }
+
+    fn status_server(status: &'static str) -> String {
+        test_server::spawn(1, move |_, stream| test_server::respond(stream, status, &[], status.as_bytes())).0
+    }
+
+    #[test]
+    fn fails_over_on_server_error() {
+        let urls = [status_server("503 Service Unavailable"), status_server("200 OK")];
+        let (requested, res) = get_with_failover(&urls);
+        assert_eq!(res.unwrap(), "200 OK");
+        assert_eq!(requested, urls);
+    }
+
+    #[test]
+    fn fails_over_on_connection_error() {
+        // Nothing is listening on the port once the listener is dropped
+        let dead_url = format!("http://{}", std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap());
+        let urls = [dead_url, status_server("200 OK")];
+        let (requested, res) = get_with_failover(&urls);
+        assert_eq!(res.unwrap(), "200 OK");
+        assert_eq!(requested, urls);
+    }
+
+    #[test]
+    fn no_failover_on_client_error() {
+        let urls = [status_server("404 Not Found"), "http://127.0.0.1:1".to_owned()];
+        let (requested, res) = get_with_failover(&urls);
+        assert!(matches!(res, Err(Error::HttpError(e)) if matches!(*e, ureq::Error::Status(404, _))));
+        assert_eq!(requested, urls[..1]);
+    }
+
+    #[test]
+    fn loopback_hosts() {
+        assert!(is_loopback("http://localhost:14366/translate"));
+        assert!(is_loopback("http://127.0.0.1:5000"));
+        assert!(is_loopback("http://[::1]:5000"));
+        assert!(!is_loopback("https://api.deepl.com/v2/translate"));
+        assert!(!is_loopback("http://192.168.1.2:5000"));
+        assert!(!is_loopback("not a url"));
+    }
+}
+
+/// Minimal HTTP server on a local port for testing the clients, serves one request per connection.
+#[cfg(test)]
+pub mod test_server {
+    use std::{io::{BufRead, BufReader, Read, Write}, net::{TcpListener, TcpStream}, thread::JoinHandle};
+
+    pub struct Request {
+        pub method: String,
+        pub path: String,
+        pub headers: Vec<(String, String)>,
+        pub body: Vec<u8>
+    }
+
+    impl Request {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers.iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(name))
+                .map(|(_, v)| v.as_str())
+        }
+
+        pub fn json(&self) -> serde_json::Value {
+            serde_json::from_slice(&self.body).expect("json body")
+        }
+    }
+
+    fn read_request(stream: &mut TcpStream) -> Option<Request> {
+        let mut reader = BufReader::new(stream);
+        let mut line = String::new();
+        reader.read_line(&mut line).ok()?;
+        let mut parts = line.split_whitespace();
+        let method = parts.next()?.to_owned();
+        let path = parts.next()?.to_owned();
+
+        let mut headers = Vec::new();
+        loop {
+            line.clear();
+            reader.read_line(&mut line).ok()?;
+            let Some((name, value)) = line.trim_end().split_once(':') else { break };
+            headers.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+
+        let mut request = Request { method, path, headers, body: Vec::new() };
+        let len = request.header("Content-Length").and_then(|s| s.parse().ok()).unwrap_or(0);
+        request.body.resize(len, 0);
+        reader.read_exact(&mut request.body).ok()?;
+        Some(request)
+    }
+
+    /// Writes a complete response and closes the connection.
+    pub fn respond(stream: &mut TcpStream, status: &str, headers: &[(&str, &str)], body: &[u8]) {
+        let mut head = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n", status, body.len());
+        for (name, value) in headers {
+            head += &format!("{}: {}\r\n", name, value);
+        }
+        head += "\r\n";
+        _ = stream.write_all(head.as_bytes());
+        _ = stream.write_all(body);
+    }
+
+    /// Serves `count` connections with `handler`. Returns the server's base url ("http://127.0.0.1:port")
+    /// and a handle that returns the requests that were received.
+    pub fn spawn(count: usize, handler: impl Fn(&Request, &mut TcpStream) + Send + 'static) -> (String, JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let Some(request) = read_request(&mut stream) else { continue };
+                handler(&request, &mut stream);
+                requests.push(request);
+            }
+            requests
+        });
+        (url, handle)
+    }
+
+    /// Responds to every request with the same JSON body.
+    pub fn spawn_json(count: usize, body: serde_json::Value) -> (String, JoinHandle<Vec<Request>>) {
+        spawn(count, move |_, stream| respond(stream, "200 OK", &[("Content-Type", "application/json")], body.to_string().as_bytes()))
+    }
+}
@@ -6,7 +6,7 @@ use tiny_http::{Header, Method, Request, Response, Server};
 
 use crate::{core::utils::notify_error, il2cpp::{hook::umamusume::{StoryTimelineController, StoryTimelineData}, symbols::{IList, Thread}}};
 
-use super::{Error, Gui, Hachimi};
+use super::{untranslated, Error, Gui, Hachimi};
 
 pub fn start_http(listen_all: bool) {
     std::thread::spawn(move || http_thread(listen_all));
@@ -151,6 +151,25 @@ fn on_http_request(request: &mut Request) -> Result<CommandResponse, Error> {
             if let Some(mutex) = Gui::instance() {
                 mutex.lock().unwrap().show_notification(&t!("notification.localized_data_reloaded"));
             }
+        },
+
+        Command::SaveUntranslatedReport => {
+            let path = untranslated::save_report()?;
+            return Ok(CommandResponse::UntranslatedReport {
+                path: path.to_string_lossy().into_owned(),
+                count: untranslated::count()
+            });
+        }
+
+        Command::SideloadTranslationRepo { path } => {
+            // Relative paths are relative to the data dir
+            let hachimi = Hachimi::instance();
+            let path = hachimi.get_data_path(path);
+            if !path.exists() {
+                return Ok(CommandResponse::error(format!("Path not found: {}", path.display())));
+            }
+            // Runs in the background, the result is shown as a notification
+            hachimi.tl_updater.clone().sideload(path);
         }
     }
 
@@ -182,7 +201,14 @@ enum Command {
         incremental: bool
     },
 
-    ReloadLocalizedData
+    ReloadLocalizedData,
+
+    SaveUntranslatedReport,
+
+    SideloadTranslationRepo {
+        // Repo zip or folder
+        path: String
+    }
 }
 
 #[derive(Serialize)]
@@ -196,6 +222,11 @@ enum CommandResponse {
 
     HelloWorld {
         message: &'static str
+    },
+
+    UntranslatedReport {
+        path: String,
+        count: usize
     }
 }
 
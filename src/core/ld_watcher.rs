@@ -0,0 +1,146 @@
+use std::{fs, path::{Path, PathBuf}, sync::atomic::{self, AtomicBool}, time::{Duration, Instant, SystemTime}};
+
+use fnv::FnvHashMap;
+use rust_i18n::t;
+
+use super::{utils, Gui, Hachimi};
+
+// Only the dirs are checked on every poll (saving through a temp file + rename, which most editors do, changes them)
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+// Checking every file is expensive on slow storage, so it backs off while nothing is changing
+const MAX_FULL_SCAN_INTERVAL: Duration = Duration::from_secs(32);
+// Editors tend to save in multiple steps, wait for things to settle down before reloading
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(1500);
+
+type FileTimes = FnvHashMap<PathBuf, SystemTime>;
+
+static STARTED: AtomicBool = AtomicBool::new(false);
+
+pub fn start() {
+    if STARTED.swap(true, atomic::Ordering::Relaxed) {
+        return;
+    }
+    std::thread::spawn(watcher_thread);
+}
+
+fn watcher_thread() {
+    info!("Localized data watcher started");
+
+    let (mut dir_times, mut file_times) = scan_layers(true);
+    let mut changed_files: Vec<PathBuf> = Vec::new();
+    let mut last_change: Option<Instant> = None;
+    let mut last_full_scan = Instant::now();
+    let mut full_scan_interval = POLL_INTERVAL;
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+
+        let hachimi = Hachimi::instance();
+        if !hachimi.config.load().auto_reload_localized_data {
+            continue;
+        }
+
+        let (new_dir_times, _) = scan_layers(false);
+        let dirs_changed = new_dir_times != dir_times;
+        dir_times = new_dir_times;
+        if !dirs_changed && last_change.is_none() && last_full_scan.elapsed() < full_scan_interval {
+            continue;
+        }
+
+        let (_, new_file_times) = scan_layers(true);
+        last_full_scan = Instant::now();
+        let mut changed = dirs_changed;
+        for (path, time) in new_file_times.iter() {
+            if file_times.get(path) != Some(time) {
+                changed_files.push(path.clone());
+                changed = true;
+            }
+        }
+        if file_times.keys().any(|p| !new_file_times.contains_key(p)) {
+            changed = true;
+        }
+        file_times = new_file_times;
+
+        if changed {
+            last_change = Some(Instant::now());
+            full_scan_interval = POLL_INTERVAL;
+            continue;
+        }
+        full_scan_interval = (full_scan_interval * 2).min(MAX_FULL_SCAN_INTERVAL);
+
+        let Some(time) = last_change else {
+            continue;
+        };
+        if time.elapsed() < DEBOUNCE_DURATION || hachimi.tl_updater.progress().is_some() {
+            continue;
+        }
+        last_change = None;
+
+        info!("Localized data changed, reloading");
+        hachimi.load_localized_data();
+
+        // Parsed the same way as the loader so that dicts with the wrong shape are reported too
+        let localized_data = hachimi.localized_data.load();
+        let failed_files: Vec<String> = changed_files.drain(..)
+            .filter(|p| p.extension().is_some_and(|ext| ext == "json" || ext == "po") && p.is_file())
+            .filter_map(|p| match localized_data.check_dict_file(&p) {
+                Ok(_) => None,
+                Err(e) => {
+                    error!("Failed to parse '{}': {}", p.display(), e);
+                    Some(format!("{} ({})", p.display(), e))
+                }
+            })
+            .collect();
+        drop(localized_data);
+
+        // The layers might have changed with the reload
+        (dir_times, file_times) = scan_layers(true);
+
+        if let Some(mutex) = Gui::instance() {
+            let mut gui = mutex.lock().unwrap();
+            gui.show_notification(&t!("notification.localized_data_reloaded"));
+            if !failed_files.is_empty() {
+                gui.show_notification(&t!("notification.localized_data_parse_failed", files = failed_files.join("\n")));
+            }
+        }
+    }
+}
+
+// Returns the modified times of the dirs, and of the files if `files` is set
+fn scan_layers(files: bool) -> (FileTimes, FileTimes) {
+    let mut dir_times = FnvHashMap::default();
+    let mut file_times = FnvHashMap::default();
+    for layer_path in Hachimi::instance().localized_data.load().layer_paths() {
+        scan_dir(&layer_path, &mut dir_times, files.then_some(&mut file_times));
+    }
+    (dir_times, file_times)
+}
+
+fn scan_dir(dir: &Path, dir_times: &mut FileTimes, mut file_times: Option<&mut FileTimes>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    if let Ok(time) = fs::metadata(dir).and_then(|m| m.modified()) {
+        dir_times.insert(dir.to_owned(), time);
+    }
+
+    for entry in entries.flatten() {
+        // Skip hidden and temporary files
+        if entry.file_name().to_str().map(|s| s.starts_with('.')).unwrap_or(true) {
+            continue;
+        }
+
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            continue;
+        };
+
+        if file_type.is_dir() {
+            scan_dir(&path, dir_times, file_times.as_deref_mut());
+        }
+        else if let Some(file_times) = file_times.as_deref_mut() {
+            if let Some(time) = utils::get_file_modified_time(&path) {
+                file_times.insert(path, time);
+            }
+        }
+    }
+}
@@ -0,0 +1,650 @@
+use std::{fs, path::{Path, PathBuf}, time::Instant};
+use fnv::FnvHashMap;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use super::{dict_cache, glossary::Glossary, pattern_dict::PatternDict, plurals, po, Error};
+
+#[derive(Default)]
+pub struct LocalizedData {
+    pub config: LocalizedDataConfig,
+    path: Option<PathBuf>,
+
+    pub localize_dict: FnvHashMap<String, String>,
+    pub hashed_dict: FnvHashMap<u64, String>,
+    pub text_data_dict: FnvHashMap<i32, FnvHashMap<i32, String>>, // {"category": {"index": "text"}}
+    pub character_system_text_dict: FnvHashMap<i32, FnvHashMap<i32, String>>, // {"character_id": {"voice_id": "text"}}
+    pub race_jikkyo_comment_dict: FnvHashMap<i32, String>, // {"id": "text"}
+    pub race_jikkyo_message_dict: FnvHashMap<i32, String>, // {"id": "text"}
+    pub sql_table_dicts: FnvHashMap<String, FnvHashMap<String, String>>, // {"table": {"key1,key2": "text"}}
+    pub pattern_dict: PatternDict,
+    pub glossary: Glossary,
+    assets_path: Option<PathBuf>,
+
+    // Highest priority first
+    overlay_paths: Vec<PathBuf>,
+    overlay_assets_paths: Vec<PathBuf>,
+    fallback: Option<Box<LocalizedData>>,
+
+    pub plural_form: plurals::Resolver,
+    pub ordinal_form: plurals::Resolver
+}
+
+impl LocalizedData {
+    /// Loads the localized data dir at `path` with the overlay dirs on top of it (highest priority first).
+    pub fn load(path: Option<PathBuf>, overlay_paths: Vec<PathBuf>) -> Result<LocalizedData, Error> {
+        let start = Instant::now();
+        let config = if let Some(ld_path) = &path {
+            let ld_config_path = ld_path.join("config.json");
+            if fs::metadata(&ld_config_path).is_ok() {
+                Self::load_config_static(&ld_config_path)?
+            }
+            else {
+                warn!("Localized data config not found");
+                LocalizedDataConfig::default()
+            }
+        }
+        else {
+            LocalizedDataConfig::default()
+        };
+
+        let plural_form = Self::parse_form_or_default(&config.plural_form, plurals::Resolver::parse_plural_form)?;
+        let ordinal_form = Self::parse_form_or_default(&config.ordinal_form, plurals::Resolver::parse_ordinal_form)?;
+
+        let fallback = path.as_ref()
+            .map(|p| Self::load_fallback(p, &config, 0))
+            .unwrap_or_default();
+
+        let mut localized_data = Self::new_pack(config, path, overlay_paths, fallback, plural_form, ordinal_form);
+        localized_data.pattern_dict.compile();
+        localized_data.glossary.compile();
+
+        info!("Loaded localized data in {}ms", start.elapsed().as_millis());
+        Ok(localized_data)
+    }
+
+    fn new_pack(
+        config: LocalizedDataConfig, path: Option<PathBuf>, overlay_paths: Vec<PathBuf>,
+        mut fallback: Option<Box<LocalizedData>>, plural_form: plurals::Resolver, ordinal_form: plurals::Resolver
+    ) -> LocalizedData {
+        let overlay_assets_paths = config.assets_dir.as_ref()
+            .map(|dir| overlay_paths.iter().map(|p| p.join(dir)).collect())
+            .unwrap_or_default();
+
+        LocalizedData {
+            localize_dict: Self::load_pack_dict(
+                &path, &overlay_paths, config.localize_dict.as_ref(),
+                fallback.as_mut().map(|f| &mut f.localize_dict)
+            ),
+            hashed_dict: Self::load_pack_dict(
+                &path, &overlay_paths, config.hashed_dict.as_ref(),
+                fallback.as_mut().map(|f| &mut f.hashed_dict)
+            ),
+            text_data_dict: Self::load_pack_dict(
+                &path, &overlay_paths, config.text_data_dict.as_ref(),
+                fallback.as_mut().map(|f| &mut f.text_data_dict)
+            ),
+            character_system_text_dict: Self::load_pack_dict(
+                &path, &overlay_paths, config.character_system_text_dict.as_ref(),
+                fallback.as_mut().map(|f| &mut f.character_system_text_dict)
+            ),
+            race_jikkyo_comment_dict: Self::load_pack_dict(
+                &path, &overlay_paths, config.race_jikkyo_comment_dict.as_ref(),
+                fallback.as_mut().map(|f| &mut f.race_jikkyo_comment_dict)
+            ),
+            race_jikkyo_message_dict: Self::load_pack_dict(
+                &path, &overlay_paths, config.race_jikkyo_message_dict.as_ref(),
+                fallback.as_mut().map(|f| &mut f.race_jikkyo_message_dict)
+            ),
+            sql_table_dicts: config.sql_tables.iter()
+                .map(|table| (table.table.clone(), Self::load_pack_dict(
+                    &path, &overlay_paths, Some(&table.dict),
+                    fallback.as_mut().and_then(|f| f.sql_table_dicts.get_mut(&table.table))
+                )))
+                .collect(),
+            pattern_dict: Self::load_pack_dict(
+                &path, &overlay_paths, config.pattern_dict.as_ref(),
+                fallback.as_mut().map(|f| &mut f.pattern_dict)
+            ),
+            glossary: Self::load_pack_dict(
+                &path, &overlay_paths, config.glossary_dict.as_ref(),
+                fallback.as_mut().map(|f| &mut f.glossary)
+            ),
+            assets_path: path.as_ref()
+                .map(|p| config.assets_dir.as_ref()
+                    .map(|dir| p.join(dir))
+                )
+                .unwrap_or_default(),
+
+            overlay_paths,
+            overlay_assets_paths,
+            fallback,
+
+            plural_form,
+            ordinal_form,
+
+            config,
+            path
+        }
+    }
+
+    fn load_config_static(config_path: &Path) -> Result<LocalizedDataConfig, Error> {
+        let json = fs::read_to_string(config_path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    const MAX_FALLBACK_DEPTH: usize = 4;
+    fn load_fallback(ld_path: &Path, config: &LocalizedDataConfig, depth: usize) -> Option<Box<LocalizedData>> {
+        let fallback_dir = config.fallback_dir.as_ref()?;
+        if depth >= Self::MAX_FALLBACK_DEPTH {
+            warn!("Too many fallback levels, ignoring fallback '{}'", fallback_dir);
+            return None;
+        }
+
+        let fallback_path = ld_path.join(fallback_dir);
+        let fallback_config = match Self::load_config_static(&fallback_path.join("config.json")) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to load fallback config in '{}': {}", fallback_path.display(), e);
+                return None;
+            }
+        };
+
+        // Only the dicts and assets are used from the fallback, the rest of its config is ignored
+        let fallback = Self::load_fallback(&fallback_path, &fallback_config, depth + 1);
+        Some(Box::new(Self::new_pack(
+            fallback_config, Some(fallback_path), Vec::new(), fallback,
+            plurals::Resolver::default(), plurals::Resolver::default()
+        )))
+    }
+
+    /// Loads a layered dict on top of the fallback's dict (which is moved out of the fallback).
+    fn load_pack_dict<T: DeserializeOwned + Serialize + LayeredDict + Default, P: AsRef<Path>>(
+        ld_path_opt: &Option<PathBuf>, overlay_paths: &[PathBuf], rel_path_opt: Option<P>, fallback_dict: Option<&mut T>
+    ) -> T {
+        let dict_opt = Self::load_layered_dict_static(ld_path_opt, overlay_paths, rel_path_opt);
+        let Some(fallback_dict) = fallback_dict else {
+            return dict_opt.unwrap_or_default();
+        };
+
+        let mut merged_dict = std::mem::take(fallback_dict);
+        if let Some(dict) = dict_opt {
+            merged_dict.merge(dict);
+        }
+        merged_dict
+    }
+
+    fn read_dict_file(path: &Path, silent_fs_error: bool) -> Option<Vec<u8>> {
+        match fs::read(path) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                if !silent_fs_error {
+                    error!("Failed to read '{}': {}", path.display(), e);
+                }
+                None
+            }
+        }
+    }
+
+    fn try_parse_dict<T: DeserializeOwned>(path: &Path, data: &[u8]) -> Result<T, Error> {
+        if path.extension().is_some_and(|ext| ext == "po") {
+            std::str::from_utf8(data)
+                .map_err(|e| Error::RuntimeError(e.to_string()))
+                .and_then(po::parse)
+                .and_then(|entries| po::to_dict_value(&entries))
+                .and_then(|value| Ok(serde_json::from_value::<T>(value)?))
+        }
+        else {
+            serde_json::from_slice::<T>(data).map_err(Error::from)
+        }
+    }
+
+    /// Reads and parses a dict file (JSON or PO), without going through the dict cache.
+    pub fn parse_dict_file<T: DeserializeOwned>(path: &Path) -> Result<T, Error> {
+        let data = fs::read(path)?;
+        Self::try_parse_dict(path, &data)
+    }
+
+    fn parse_dict<T: DeserializeOwned>(path: &Path, data: &[u8]) -> Option<T> {
+        match Self::try_parse_dict(path, data) {
+            Ok(v) => Some(v),
+            Err(e) => {
+                error!("Failed to parse '{}': {}", path.display(), e);
+                None
+            }
+        }
+    }
+
+    fn load_dict_static_ex<T: DeserializeOwned, P: AsRef<Path>>(ld_path_opt: &Option<PathBuf>, rel_path_opt: Option<P>, silent_fs_error: bool) -> Option<T> {
+        let ld_path = ld_path_opt.as_ref()?;
+        let rel_path = rel_path_opt?;
+
+        let path = ld_path.join(rel_path);
+        let data = Self::read_dict_file(&path, silent_fs_error)?;
+        Self::parse_dict(&path, &data)
+    }
+
+    /// Same as load_dict_static_ex, but goes through the binary dict cache.
+    fn load_cached_dict_static<T: DeserializeOwned + Serialize, P: AsRef<Path>>(
+        ld_path_opt: &Option<PathBuf>, rel_path_opt: Option<P>, silent_fs_error: bool
+    ) -> Option<T> {
+        if !dict_cache::is_enabled() {
+            return Self::load_dict_static_ex(ld_path_opt, rel_path_opt, silent_fs_error);
+        }
+
+        let ld_path = ld_path_opt.as_ref()?;
+        let rel_path = rel_path_opt?;
+        let path = ld_path.join(&rel_path);
+        let Some(cache_path) = dict_cache::get_path(ld_path, rel_path.as_ref()) else {
+            return Self::load_dict_static_ex(ld_path_opt, Some(rel_path), silent_fs_error);
+        };
+
+        let start = Instant::now();
+        let stamp = dict_cache::SourceStamp::get(&path);
+        if let Some(dict) = stamp.as_ref().and_then(|stamp| dict_cache::load_if_unchanged(&cache_path, stamp)) {
+            debug!("Loaded '{}' from cache in {}ms", path.display(), start.elapsed().as_millis());
+            return Some(dict);
+        }
+
+        let data = Self::read_dict_file(&path, silent_fs_error)?;
+        let hash = blake3::hash(&data);
+        if let Some(dict) = dict_cache::load::<T>(&cache_path, &hash) {
+            debug!("Loaded '{}' from cache in {}ms (source touched)", path.display(), start.elapsed().as_millis());
+            // Update the stamp so that it doesn't need to be hashed again
+            dict_cache::save(&cache_path, stamp.as_ref(), &hash, &dict);
+            return Some(dict);
+        }
+
+        let dict = Self::parse_dict(&path, &data)?;
+        debug!("Parsed '{}' in {}ms", path.display(), start.elapsed().as_millis());
+        dict_cache::save(&cache_path, stamp.as_ref(), &hash, &dict);
+        Some(dict)
+    }
+
+    fn load_dict_static<T: DeserializeOwned, P: AsRef<Path>>(ld_path_opt: &Option<PathBuf>, rel_path_opt: Option<P>) -> Option<T> {
+        Self::load_dict_static_ex(ld_path_opt, rel_path_opt, false)
+    }
+
+    /// Loads the dict from the base dir and merges every overlay that has it on top.
+    fn load_layered_dict_static<T: DeserializeOwned + Serialize + LayeredDict, P: AsRef<Path>>(
+        ld_path_opt: &Option<PathBuf>, overlay_paths: &[PathBuf], rel_path_opt: Option<P>
+    ) -> Option<T> {
+        let rel_path = rel_path_opt?;
+        let mut dict_opt: Option<T> = Self::load_cached_dict_static(ld_path_opt, Some(&rel_path), false);
+
+        // Lowest priority first so that higher ones overwrite its entries
+        for overlay_path in overlay_paths.iter().rev() {
+            let Some(overlay_dict) = Self::load_cached_dict_static(&Some(overlay_path.clone()), Some(&rel_path), true) else {
+                continue;
+            };
+
+            if let Some(dict) = dict_opt.as_mut() {
+                dict.merge(overlay_dict);
+            }
+            else {
+                dict_opt = Some(overlay_dict);
+            }
+        }
+
+        dict_opt
+    }
+
+    /// Searches the overlays top-down, then the base dir, then the fallback.
+    pub fn load_dict<T: DeserializeOwned, P: AsRef<Path>>(&self, rel_path_opt: Option<P>) -> Option<T> {
+        let rel_path = rel_path_opt?;
+        for ld_path in self.overlay_paths.iter().chain(self.path.iter()) {
+            if ld_path.join(&rel_path).is_file() {
+                return Self::load_dict_static(&Some(ld_path.clone()), Some(&rel_path));
+            }
+        }
+        self.fallback.as_ref()?.load_dict(Some(rel_path))
+    }
+
+    /// Lists the existing files of a dict in the same order as load_dict searches them.
+    /// The fallbacks use the dict names from their own config.
+    pub fn dict_layer_files(&self, dict_name: &dyn Fn(&LocalizedDataConfig) -> Option<&String>) -> Vec<PathBuf> {
+        let mut files = Vec::new();
+        if let Some(rel_path) = dict_name(&self.config) {
+            for ld_path in self.overlay_paths.iter().chain(self.path.iter()) {
+                let path = ld_path.join(rel_path);
+                if path.is_file() {
+                    files.push(path);
+                }
+            }
+        }
+        if let Some(fallback) = &self.fallback {
+            files.extend(fallback.dict_layer_files(dict_name));
+        }
+        files
+    }
+
+    fn assets_layers(&self) -> impl Iterator<Item = &PathBuf> {
+        self.overlay_assets_paths.iter().chain(self.assets_path.iter())
+    }
+
+    /// Searches the overlays top-down, then the base assets dir, then the fallback.
+    pub fn load_assets_dict<T: DeserializeOwned, P: AsRef<Path>>(&self, rel_path_opt: Option<P>) -> Option<T> {
+        let rel_path = rel_path_opt?;
+        for assets_path in self.assets_layers() {
+            if assets_path.join(&rel_path).is_file() {
+                return Self::load_dict_static_ex(&Some(assets_path.clone()), Some(&rel_path), true);
+            }
+        }
+        self.fallback.as_ref()?.load_assets_dict(Some(rel_path))
+    }
+
+    fn parse_form_or_default(
+        opt: &Option<String>, parse: fn(&str) -> Result<plurals::Resolver, Error>
+    ) -> Result<plurals::Resolver, Error> {
+        if let Some(form) = opt {
+            parse(form)
+        }
+        else {
+            Ok(plurals::Resolver::Function(|_| 0))
+        }
+    }
+
+    fn find_assets_path(&self, rel_path: &Path) -> Option<PathBuf> {
+        for assets_path in self.assets_layers() {
+            let path = assets_path.join(rel_path);
+            if path.is_file() {
+                return Some(path);
+            }
+
+            // Textures might only be shipped as a diff
+            let mut diff_path = path.clone();
+            diff_path.set_extension("diff.png");
+            if diff_path.is_file() {
+                return Some(path);
+            }
+        }
+
+        self.fallback.as_ref()?.find_assets_path(rel_path)
+    }
+
+    /// Returns the path in the highest layer that has the file, or the path in the base assets dir
+    /// if none of the layers have it.
+    pub fn get_assets_path<P: AsRef<Path>>(&self, rel_path: P) -> Option<PathBuf> {
+        self.find_assets_path(rel_path.as_ref())
+            .or_else(|| self.assets_path.as_ref().map(|p| p.join(rel_path)))
+    }
+
+    /// Every dir that data is loaded from, including the fallbacks.
+    pub fn layer_paths(&self) -> Vec<PathBuf> {
+        let mut paths: Vec<PathBuf> = self.overlay_paths.iter().chain(self.path.iter()).cloned().collect();
+        if let Some(fallback) = &self.fallback {
+            paths.extend(fallback.layer_paths());
+        }
+        paths
+    }
+
+    /// Parses a file in one of the layers as the dict it's loaded as (just the JSON syntax for files
+    /// that aren't dicts, e.g. asset metadata).
+    pub fn check_dict_file(&self, path: &Path) -> Result<(), Error> {
+        let Some(rel_path) = self.overlay_paths.iter().chain(self.path.iter())
+            .find_map(|ld_path| path.strip_prefix(ld_path).ok())
+        else {
+            return match &self.fallback {
+                Some(fallback) => fallback.check_dict_file(path),
+                None => Ok(())
+            };
+        };
+
+        let config = &self.config;
+        let is = |dict: &Option<String>| dict.as_ref().is_some_and(|d| Path::new(d) == rel_path);
+        if rel_path == Path::new("config.json") {
+            Self::parse_dict_file::<LocalizedDataConfig>(path)?;
+        }
+        else if is(&config.localize_dict) || config.sql_tables.iter().any(|t| Path::new(&t.dict) == rel_path) {
+            Self::parse_dict_file::<FnvHashMap<String, String>>(path)?;
+        }
+        else if is(&config.hashed_dict) {
+            Self::parse_dict_file::<FnvHashMap<u64, String>>(path)?;
+        }
+        else if is(&config.text_data_dict) || is(&config.character_system_text_dict) {
+            Self::parse_dict_file::<FnvHashMap<i32, FnvHashMap<i32, String>>>(path)?;
+        }
+        else if is(&config.race_jikkyo_comment_dict) || is(&config.race_jikkyo_message_dict) {
+            Self::parse_dict_file::<FnvHashMap<i32, String>>(path)?;
+        }
+        else if is(&config.pattern_dict) {
+            Self::parse_dict_file::<PatternDict>(path)?;
+        }
+        else if is(&config.glossary_dict) {
+            Self::parse_dict_file::<Glossary>(path)?;
+        }
+        else {
+            Self::parse_dict_file::<serde_json::Value>(path)?;
+        }
+        Ok(())
+    }
+
+    pub fn get_data_path<P: AsRef<Path>>(&self, rel_path: P) -> Option<PathBuf> {
+        self.path.as_ref().map(|p| p.join(rel_path))
+    }
+
+    pub fn load_asset_metadata<P: AsRef<Path>>(&self, rel_path: P) -> AssetMetadata {
+        let mut path = rel_path.as_ref().to_owned();
+        path.set_extension("json");
+        self.load_assets_dict::<AssetInfo<()>, _>(Some(path)).unwrap_or_default().metadata()
+    }
+
+    pub fn load_asset_info<P: AsRef<Path>, T: DeserializeOwned>(&self, rel_path: P) -> AssetInfo<T> {
+        let mut path = rel_path.as_ref().to_owned();
+        path.set_extension("json");
+        self.load_assets_dict(Some(path)).unwrap_or_default()
+    }
+}
+
+trait LayeredDict {
+    /// Merges the other dict into this one, overwriting existing entries.
+    fn merge(&mut self, other: Self);
+}
+
+impl<K: Eq + std::hash::Hash> LayeredDict for FnvHashMap<K, String> {
+    fn merge(&mut self, other: Self) {
+        self.extend(other);
+    }
+}
+
+impl LayeredDict for PatternDict {
+    fn merge(&mut self, other: Self) {
+        self.prepend(other);
+    }
+}
+
+impl LayeredDict for Glossary {
+    fn merge(&mut self, other: Self) {
+        Glossary::merge(self, other);
+    }
+}
+
+impl<K: Eq + std::hash::Hash, K2: Eq + std::hash::Hash> LayeredDict for FnvHashMap<K, FnvHashMap<K2, String>> {
+    fn merge(&mut self, other: Self) {
+        for (key, sub_dict) in other {
+            self.entry(key).or_default().merge(sub_dict);
+        }
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct LocalizedDataConfig {
+    pub localize_dict: Option<String>,
+    pub hashed_dict: Option<String>,
+    pub text_data_dict: Option<String>,
+    pub character_system_text_dict: Option<String>,
+    pub race_jikkyo_comment_dict: Option<String>,
+    pub race_jikkyo_message_dict: Option<String>,
+    // Ordered regex patterns for text that can't be matched exactly (see pattern_dict.rs)
+    pub pattern_dict: Option<String>,
+    // Terms that are kept consistent in machine translations (see glossary.rs)
+    pub glossary_dict: Option<String>,
+    // Text replacement for other master tables (see SqlTableConfig)
+    #[serde(default)]
+    pub sql_tables: Vec<SqlTableConfig>,
+    pub assets_dir: Option<String>,
+    // Another localized data dir (relative to this one) that missing entries and assets are taken from.
+    // Can be chained.
+    pub fallback_dir: Option<String>,
+    #[serde(default)]
+    pub extra_asset_bundle: OsOption<String>,
+    pub replacement_font_name: Option<String>,
+
+    pub plural_form: Option<String>,
+    pub ordinal_form: Option<String>,
+    #[serde(default)]
+    pub ordinal_types: Vec<String>,
+    #[serde(default)]
+    pub months: Vec<String>,
+    pub month_text_format: Option<String>,
+
+    #[serde(default)]
+    pub use_text_wrapper: bool,
+    // Predefined line widths are counts of cjk characters.
+    // 1 cjk char = 2 columns, so setting this value to 2 replicates the default behaviour.
+    pub line_width_multiplier: Option<f32>,
+
+    #[serde(default)]
+    pub auto_adjust_story_clip_length: bool,
+    pub story_line_count_offset: Option<i32>,
+    pub text_frame_line_spacing_multiplier: Option<f32>,
+    pub text_frame_font_size_multiplier: Option<f32>,
+    pub skill_list_item_desc_font_size_multiplier: Option<f32>,
+    #[serde(default)]
+    pub text_common_allow_overflow: bool,
+    #[serde(default)]
+    pub now_loading_comic_title_ellipsis: bool,
+
+    #[serde(default)]
+    pub remove_ruby: bool,
+    pub character_note_top_gallery_button: Option<UITextConfig>,
+    pub character_note_top_talk_gallery_button: Option<UITextConfig>,
+
+    pub news_url: Option<String>,
+
+    // RESERVED
+    #[serde(default)]
+    pub _debug: i32
+}
+
+// Declares a master table that doesn't have a dedicated dict, e.g.
+// { "table": "some_table", "key_columns": ["group_id", "id"], "text_column": "name", "dict": "some_table_dict.json" }
+// would use a dict like {"1,1001": "text"}. Key values are joined with commas in the order they're declared in.
+// Tables that Hachimi handles natively can't be redeclared.
+#[derive(Deserialize, Clone)]
+pub struct SqlTableConfig {
+    pub table: String,
+    // Columns that are either bound as params (WHERE column = ?) or selected in the query
+    pub key_columns: Vec<String>,
+    pub text_column: String,
+    pub dict: String
+}
+
+#[derive(Deserialize, Clone)]
+pub struct UITextConfig {
+    pub text: Option<String>,
+    pub font_size: Option<i32>,
+    pub line_spacing: Option<f32>
+}
+
+impl Default for LocalizedDataConfig {
+    fn default() -> Self {
+        default_serde_instance().expect("default instance")
+    }
+}
+
+#[derive(Deserialize, Default, Clone)]
+pub struct OsOption<T> {
+    #[cfg(target_os = "android")]
+    android: Option<T>,
+
+    #[cfg(target_os = "windows")]
+    windows: Option<T>,
+
+    // Host builds (e.g. tools) don't have any platform specific options
+    #[cfg(not(any(target_os = "android", target_os = "windows")))]
+    #[serde(skip)]
+    host: Option<T>
+}
+
+impl<T> OsOption<T> {
+    pub fn as_ref(&self) -> Option<&T> {
+        #[cfg(target_os = "android")]
+        return self.android.as_ref();
+
+        #[cfg(target_os = "windows")]
+        return self.windows.as_ref();
+
+        #[cfg(not(any(target_os = "android", target_os = "windows")))]
+        return self.host.as_ref();
+    }
+}
+
+#[derive(Deserialize)]
+pub struct AssetInfo<T> {
+    #[cfg(target_os = "android")]
+    #[serde(default)]
+    android: AssetMetadata,
+
+    #[cfg(target_os = "windows")]
+    #[serde(default)]
+    windows: AssetMetadata,
+
+    #[cfg(not(any(target_os = "android", target_os = "windows")))]
+    #[serde(skip)]
+    host: AssetMetadata,
+
+    pub data: Option<T>
+}
+
+// Can't derive(Default), see rust-lang/rust#26925
+impl<T> Default for AssetInfo<T> {
+    fn default() -> Self {
+        Self {
+            #[cfg(target_os = "android")]
+            android: Default::default(),
+
+            #[cfg(target_os = "windows")]
+            windows: Default::default(),
+
+            #[cfg(not(any(target_os = "android", target_os = "windows")))]
+            host: Default::default(),
+
+            data: None
+        }
+    }
+}
+
+impl<T> AssetInfo<T> {
+    pub fn metadata(self) -> AssetMetadata {
+        #[cfg(target_os = "android")]
+        return self.android;
+
+        #[cfg(target_os = "windows")]
+        return self.windows;
+
+        #[cfg(not(any(target_os = "android", target_os = "windows")))]
+        return self.host;
+    }
+
+    pub fn metadata_ref(&self) -> &AssetMetadata {
+        #[cfg(target_os = "android")]
+        return &self.android;
+
+        #[cfg(target_os = "windows")]
+        return &self.windows;
+
+        #[cfg(not(any(target_os = "android", target_os = "windows")))]
+        return &self.host;
+    }
+}
+
+#[derive(Deserialize, Clone, Default)]
+pub struct AssetMetadata {
+    pub bundle_name: Option<String>
+}
+
+pub fn default_serde_instance<'a, T: Deserialize<'a>>() -> Option<T> {
+    let empty_data = std::iter::empty::<((), ())>();
+    let empty_deserializer = serde::de::value::MapDeserializer::<_, serde::de::value::Error>::new(empty_data);
+    T::deserialize(empty_deserializer).ok()
+}
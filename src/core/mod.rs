@@ -1,5 +1,8 @@
 pub mod hachimi;
 pub use hachimi::Hachimi;
+pub mod localized_data;
+pub mod dict_cache;
+pub mod asset_dicts;
 
 mod error;
 pub use error::Error;
@@ -11,8 +14,11 @@ pub mod template;
 pub mod gui;
 pub use gui::Gui;
 
+pub mod pattern_dict;
+pub mod glossary;
 pub mod plurals;
-mod template_filters;
+pub mod po;
+pub mod template_filters;
 
 #[macro_use] pub mod interceptor;
 pub use interceptor::Interceptor;
@@ -22,8 +28,11 @@ pub mod http;
 pub mod tl_repo;
 pub mod log;
 mod ipc;
+mod ld_watcher;
+pub mod untranslated;
+pub mod source_hash;
+pub mod asset_skeleton;
 
-mod sugoi_client;
-pub use sugoi_client::SugoiClient;
+pub mod mt;
 
 pub mod plugin_api;
\ No newline at end of file
@@ -0,0 +1,155 @@
+/*
+    Persistent cache of machine translations, so that the same text is never sent twice.
+    Entries are keyed by a hash of the backend, target language and source text, and are stored in
+    mt_cache.json in the data dir. Changes are flushed in the background every FLUSH_INTERVAL,
+    when the config is reloaded and when the game exits (on Windows).
+
+    The cache can be exported as a localize_dict (for the entries that came from Localize) and
+    a hashed_dict, so machine output can be reviewed and turned into real translations.
+*/
+use std::{collections::BTreeMap, fs, hash::Hasher, path::Path, sync::Mutex, time::Duration};
+
+use fnv::{FnvHashMap, FnvHasher};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::core::{utils, Error, Hachimi};
+
+use super::{MtBackend, MtConfig};
+
+pub const FILENAME: &str = "mt_cache.json";
+pub const EXPORT_DIR: &str = "mt_export";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Entry {
+    pub source: String,
+    pub text: String,
+    pub backend: MtBackend,
+    pub target_lang: String,
+    // TextId name, if the text came from Localize
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>
+}
+
+#[derive(Default)]
+struct Cache {
+    entries: BTreeMap<String, Entry>,
+    dirty: bool
+}
+
+static CACHE: Lazy<Mutex<Cache>> = Lazy::new(|| {
+    std::thread::spawn(|| loop {
+        std::thread::sleep(FLUSH_INTERVAL);
+        flush_or_log();
+    });
+
+    let path = Hachimi::instance().get_data_path(FILENAME);
+    let entries = match fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            error!("Failed to parse MT cache: {}", e);
+            BTreeMap::new()
+        }),
+        Err(_) => BTreeMap::new()
+    };
+    Mutex::new(Cache { entries, dirty: false })
+});
+
+fn get_key(config: &MtConfig, source: &str) -> String {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(serde_json::to_string(&config.backend).unwrap_or_default().as_bytes());
+    hasher.update(b"\0");
+    hasher.update(config.target_lang.as_bytes());
+    hasher.update(b"\0");
+    hasher.update(source.as_bytes());
+    let mut key = hasher.finalize().to_hex().to_string();
+    key.truncate(16);
+    key
+}
+
+/// `name` is recorded on the entry if it doesn't have one yet.
+pub fn get(config: &MtConfig, source: &str, name: Option<&str>) -> Option<String> {
+    let mut cache = CACHE.lock().unwrap();
+    let entry = cache.entries.get_mut(&get_key(config, source))?;
+    let text = entry.text.clone();
+    if entry.name.is_none() && name.is_some() {
+        entry.name = name.map(|s| s.to_owned());
+        cache.dirty = true;
+    }
+    Some(text)
+}
+
+pub fn insert(config: &MtConfig, source: &str, text: &str, name: Option<&str>) {
+    let mut cache = CACHE.lock().unwrap();
+    cache.entries.insert(get_key(config, source), Entry {
+        source: source.to_owned(),
+        text: text.to_owned(),
+        backend: config.backend,
+        target_lang: config.target_lang.clone(),
+        name: name.map(|s| s.to_owned())
+    });
+    cache.dirty = true;
+}
+
+pub fn count() -> usize {
+    CACHE.lock().unwrap().entries.len()
+}
+
+/// Writes the cache to disk if it has been loaded and has changed.
+pub fn flush() -> Result<(), Error> {
+    let Some(cache) = Lazy::get(&CACHE) else {
+        return Ok(());
+    };
+    let entries = {
+        let mut cache = cache.lock().unwrap();
+        if !cache.dirty {
+            return Ok(());
+        }
+        cache.dirty = false;
+        cache.entries.clone()
+    };
+
+    utils::write_json_file(&entries, Hachimi::instance().get_data_path(FILENAME))
+}
+
+pub fn flush_or_log() {
+    if let Err(e) = flush() {
+        error!("Failed to save MT cache: {}", e);
+    }
+}
+
+// Same hash as Il2CppStringExt::hash (FNV-1a of the UTF-16 data)
+fn hash_text(text: &str) -> u64 {
+    let mut hasher = FnvHasher::default();
+    for c in text.encode_utf16() {
+        hasher.write(&c.to_le_bytes());
+    }
+    hasher.finish()
+}
+
+/// Exports the entries for the current backend and target language to `out_dir`
+/// (localize_dict.json and hashed_dict.json). Returns the number of entries exported.
+pub fn export(config: &MtConfig, out_dir: &Path) -> Result<usize, Error> {
+    let mut localize_dict = BTreeMap::new();
+    let mut hashed_dict = FnvHashMap::default();
+    let mut count = 0;
+    {
+        let cache = CACHE.lock().unwrap();
+        for entry in cache.entries.values() {
+            if entry.backend != config.backend || entry.target_lang != config.target_lang {
+                continue;
+            }
+
+            if let Some(name) = &entry.name {
+                localize_dict.insert(name.clone(), entry.text.clone());
+            }
+            hashed_dict.insert(hash_text(&entry.source), entry.text.clone());
+            count += 1;
+        }
+    }
+
+    fs::create_dir_all(out_dir)?;
+    utils::write_json_file(&localize_dict, out_dir.join("localize_dict.json"))?;
+    utils::write_json_file(&hashed_dict.into_iter().collect::<BTreeMap<_, _>>(), out_dir.join("hashed_dict.json"))?;
+    Ok(count)
+}
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::{http, Error};
+
+use super::{MtClient, MtConfig};
+
+pub struct DeeplClient {
+    agent: ureq::Agent,
+    url: String,
+    config: MtConfig
+}
+
+impl DeeplClient {
+    pub fn new(url: String, config: &MtConfig) -> DeeplClient {
+        DeeplClient {
+            agent: http::agent_for(&url),
+            url,
+            config: config.clone()
+        }
+    }
+}
+
+impl MtClient for DeeplClient {
+    fn translate(&self, content: &[String]) -> Result<Vec<String>, Error> {
+        // The API key goes in the headers (Authorization: DeepL-Auth-Key xxx)
+        let res: Response = self.config.build_request(&self.agent, &self.url)
+            .send_json(Request {
+                text: content,
+                source_lang: self.config.source_lang.to_uppercase(),
+                target_lang: self.config.target_lang.to_uppercase()
+            })?
+            .into_json()?;
+        Ok(res.translations.into_iter().map(|t| t.text).collect())
+    }
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    text: &'a [String],
+    source_lang: String,
+    target_lang: String
+}
+
+#[derive(Deserialize)]
+struct Response {
+    translations: Vec<Translation>
+}
+
+#[derive(Deserialize)]
+struct Translation {
+    text: String
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::http::test_server;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (url, server) = test_server::spawn_json(1, serde_json::json!({
+            "translations": [
+                { "detected_source_language": "JA", "text": "Hello" },
+                { "detected_source_language": "JA", "text": "World" }
+            ]
+        }));
+        let mut config = MtConfig::default();
+        config.headers.insert("Authorization".to_owned(), "DeepL-Auth-Key test".to_owned());
+        let client = DeeplClient { agent: ureq::Agent::new(), url: url + "/v2/translate", config };
+
+        let res = client.translate(&["こんにちは".to_owned(), "世界".to_owned()]).unwrap();
+        assert_eq!(res, ["Hello", "World"]);
+
+        let request = server.join().unwrap().pop().unwrap();
+        assert_eq!(request.path, "/v2/translate");
+        assert_eq!(request.header("Authorization"), Some("DeepL-Auth-Key test"));
+        assert_eq!(request.json(), serde_json::json!({
+            "text": ["こんにちは", "世界"],
+            "source_lang": "JA",
+            "target_lang": "EN"
+        }));
+    }
+}
@@ -0,0 +1,81 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::{http, Error};
+
+use super::{MtClient, MtConfig};
+
+pub struct LibreTranslateClient {
+    agent: ureq::Agent,
+    url: String,
+    config: MtConfig
+}
+
+impl LibreTranslateClient {
+    pub fn new(url: String, config: &MtConfig) -> LibreTranslateClient {
+        LibreTranslateClient {
+            agent: http::agent_for(&url),
+            url,
+            config: config.clone()
+        }
+    }
+}
+
+impl MtClient for LibreTranslateClient {
+    fn translate(&self, content: &[String]) -> Result<Vec<String>, Error> {
+        let res: Response = self.config.build_request(&self.agent, &self.url)
+            .send_json(Request {
+                q: content,
+                source: &self.config.source_lang,
+                target: &self.config.target_lang,
+                format: "text",
+                api_key: self.config.api_key.as_deref()
+            })?
+            .into_json()?;
+        Ok(res.translated_text)
+    }
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    q: &'a [String],
+    source: &'a str,
+    target: &'a str,
+    format: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_key: Option<&'a str>
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Response {
+    translated_text: Vec<String>
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::http::test_server;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (url, server) = test_server::spawn_json(1, serde_json::json!({
+            "translatedText": ["Hello", "World"]
+        }));
+        let config = MtConfig { api_key: Some("key".to_owned()), ..MtConfig::default() };
+        let client = LibreTranslateClient { agent: ureq::Agent::new(), url: url + "/translate", config };
+
+        let res = client.translate(&["こんにちは".to_owned(), "世界".to_owned()]).unwrap();
+        assert_eq!(res, ["Hello", "World"]);
+
+        let request = server.join().unwrap().pop().unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.json(), serde_json::json!({
+            "q": ["こんにちは", "世界"],
+            "source": "ja",
+            "target": "en",
+            "format": "text",
+            "api_key": "key"
+        }));
+    }
+}
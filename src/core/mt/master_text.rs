@@ -0,0 +1,123 @@
+/*
+    Auto translation for the text served through the SQL hooks (auto_translate_master_text).
+    Rows that don't have a translation are translated in the background (see queue.rs). Once a row's
+    translation is used, it's also added to the generated dicts in the mt_master_text dir of the data dir,
+    which have the same format as the localized data's dicts so they can be reviewed and shipped as is.
+*/
+use std::{collections::BTreeMap, fs, path::Path, sync::Mutex, time::Duration};
+
+use once_cell::sync::Lazy;
+use serde::de::DeserializeOwned;
+
+use crate::core::{utils, Error, Hachimi};
+
+use super::queue;
+
+pub const DIR: &str = "mt_master_text";
+const FLUSH_INTERVAL: Duration = Duration::from_secs(30);
+
+pub enum Row {
+    TextData { category: i32, index: i32 },
+    CharacterSystemText { character_id: i32, voice_id: i32 },
+    RaceJikkyoComment { id: i32 },
+    RaceJikkyoMessage { id: i32 }
+}
+
+#[derive(Default)]
+struct Dicts {
+    text_data_dict: BTreeMap<i32, BTreeMap<i32, String>>,
+    character_system_text_dict: BTreeMap<i32, BTreeMap<i32, String>>,
+    race_jikkyo_comment_dict: BTreeMap<i32, String>,
+    race_jikkyo_message_dict: BTreeMap<i32, String>,
+    dirty: bool
+}
+
+static DICTS: Lazy<Mutex<Dicts>> = Lazy::new(|| {
+    std::thread::spawn(|| loop {
+        std::thread::sleep(FLUSH_INTERVAL);
+        if let Err(e) = flush() {
+            error!("Failed to save MT master text dicts: {}", e);
+        }
+    });
+
+    // Keep adding to the dicts from the previous sessions
+    let dir = Hachimi::instance().get_data_path(DIR);
+    Mutex::new(Dicts {
+        text_data_dict: load_dict(&dir, "text_data_dict.json"),
+        character_system_text_dict: load_dict(&dir, "character_system_text_dict.json"),
+        race_jikkyo_comment_dict: load_dict(&dir, "race_jikkyo_comment_dict.json"),
+        race_jikkyo_message_dict: load_dict(&dir, "race_jikkyo_message_dict.json"),
+        dirty: false
+    })
+});
+
+fn load_dict<T: DeserializeOwned + Default>(dir: &Path, filename: &str) -> T {
+    let path = dir.join(filename);
+    match fs::read_to_string(&path) {
+        Ok(json) => serde_json::from_str(&json).unwrap_or_else(|e| {
+            error!("Failed to parse {}: {}", path.display(), e);
+            T::default()
+        }),
+        Err(_) => T::default()
+    }
+}
+
+pub fn is_enabled() -> bool {
+    Hachimi::instance().config.load().auto_translate_master_text
+}
+
+/// Returns the row's machine translation, or None if it's not available (yet).
+/// `text` returns the row's original text, it's only called if auto translation is enabled.
+pub fn translate(row: Row, text: impl FnOnce() -> String) -> Option<String> {
+    if !is_enabled() {
+        return None;
+    }
+
+    let text = text();
+    if text.is_empty() {
+        return None;
+    }
+
+    let translated = queue::translate_or_enqueue(&text, None)?;
+    add(row, &translated);
+    Some(translated)
+}
+
+fn insert<K: Ord>(dict: &mut BTreeMap<K, String>, key: K, text: &str) -> bool {
+    if dict.get(&key).is_some_and(|s| s == text) {
+        return false;
+    }
+    dict.insert(key, text.to_owned());
+    true
+}
+
+fn add(row: Row, text: &str) {
+    let mut dicts = DICTS.lock().unwrap();
+    let changed = match row {
+        Row::TextData { category, index } =>
+            insert(dicts.text_data_dict.entry(category).or_default(), index, text),
+        Row::CharacterSystemText { character_id, voice_id } =>
+            insert(dicts.character_system_text_dict.entry(character_id).or_default(), voice_id, text),
+        Row::RaceJikkyoComment { id } => insert(&mut dicts.race_jikkyo_comment_dict, id, text),
+        Row::RaceJikkyoMessage { id } => insert(&mut dicts.race_jikkyo_message_dict, id, text)
+    };
+    if changed {
+        dicts.dirty = true;
+    }
+}
+
+/// Writes the generated dicts to disk if they have changed.
+pub fn flush() -> Result<(), Error> {
+    let mut dicts = DICTS.lock().unwrap();
+    if !dicts.dirty {
+        return Ok(());
+    }
+    dicts.dirty = false;
+
+    let dir = Hachimi::instance().get_data_path(DIR);
+    fs::create_dir_all(&dir)?;
+    utils::write_json_file(&dicts.text_data_dict, dir.join("text_data_dict.json"))?;
+    utils::write_json_file(&dicts.character_system_text_dict, dir.join("character_system_text_dict.json"))?;
+    utils::write_json_file(&dicts.race_jikkyo_comment_dict, dir.join("race_jikkyo_comment_dict.json"))?;
+    utils::write_json_file(&dicts.race_jikkyo_message_dict, dir.join("race_jikkyo_message_dict.json"))
+}
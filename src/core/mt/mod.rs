@@ -0,0 +1,196 @@
+/*
+    Machine translation backends, used for auto translation.
+    The backend is picked by the mt config; the legacy sugoi_url option is still used as the
+    Sugoi backend's url if the mt config doesn't specify one.
+    Every translation goes through the persistent cache (see cache.rs) and the localized data's glossary.
+*/
+use std::{collections::BTreeMap, sync::{Arc, Mutex}};
+
+use serde::{Deserialize, Serialize};
+
+use super::{glossary::Glossary, localized_data::default_serde_instance, Error, Hachimi};
+
+mod sugoi;
+pub use sugoi::SugoiClient;
+mod libre_translate;
+pub use libre_translate::LibreTranslateClient;
+mod deepl;
+pub use deepl::DeeplClient;
+mod openai;
+pub use openai::OpenAiClient;
+pub mod cache;
+pub mod queue;
+pub mod master_text;
+
+pub trait MtClient: Send + Sync {
+    /// Translates the strings, the result must have the same length and order.
+    fn translate(&self, content: &[String]) -> Result<Vec<String>, Error>;
+}
+
+#[derive(Default, Copy, Clone, Eq, PartialEq, Deserialize, Serialize)]
+pub enum MtBackend {
+    #[serde(rename = "sugoi")]
+    #[default] Sugoi,
+
+    #[serde(rename = "libretranslate")]
+    LibreTranslate,
+
+    #[serde(rename = "deepl")]
+    Deepl,
+
+    #[serde(rename = "openai")]
+    OpenAi
+}
+
+impl MtBackend {
+    pub const CHOICES: &[(Self, &'static str)] = &[
+        (Self::Sugoi, "Sugoi"),
+        (Self::LibreTranslate, "LibreTranslate"),
+        (Self::Deepl, "DeepL"),
+        (Self::OpenAi, "OpenAI")
+    ];
+
+    pub const fn default_url(&self) -> &'static str {
+        match self {
+            MtBackend::Sugoi => "http://127.0.0.1:14366",
+            MtBackend::LibreTranslate => "http://127.0.0.1:5000/translate",
+            MtBackend::Deepl => "https://api-free.deepl.com/v2/translate",
+            MtBackend::OpenAi => "https://api.openai.com/v1/chat/completions"
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, PartialEq)]
+pub struct MtConfig {
+    #[serde(default)]
+    pub backend: MtBackend,
+    pub url: Option<String>,
+    // Extra headers sent with every request (e.g. Authorization)
+    #[serde(default)]
+    pub headers: BTreeMap<String, String>,
+    #[serde(default = "MtConfig::default_source_lang")]
+    pub source_lang: String,
+    #[serde(default = "MtConfig::default_target_lang")]
+    pub target_lang: String,
+
+    // LibreTranslate
+    pub api_key: Option<String>,
+
+    // OpenAI
+    pub model: Option<String>,
+    pub system_prompt: Option<String>
+}
+
+impl MtConfig {
+    fn default_source_lang() -> String { "ja".to_owned() }
+    fn default_target_lang() -> String { "en".to_owned() }
+
+    pub fn create_client(&self, legacy_sugoi_url: Option<&String>) -> Arc<dyn MtClient> {
+        let url = self.url.as_ref()
+            .or(legacy_sugoi_url.filter(|_| self.backend == MtBackend::Sugoi))
+            .cloned()
+            .unwrap_or_else(|| self.backend.default_url().to_owned());
+
+        match self.backend {
+            MtBackend::Sugoi => Arc::new(SugoiClient::new(url, self)),
+            MtBackend::LibreTranslate => Arc::new(LibreTranslateClient::new(url, self)),
+            MtBackend::Deepl => Arc::new(DeeplClient::new(url, self)),
+            MtBackend::OpenAi => Arc::new(OpenAiClient::new(url, self))
+        }
+    }
+
+    fn build_request(&self, agent: &ureq::Agent, url: &str) -> ureq::Request {
+        let mut request = agent.post(url).set("Content-Type", "application/json");
+        for (name, value) in self.headers.iter() {
+            request = request.set(name, value);
+        }
+        request
+    }
+}
+
+impl Default for MtConfig {
+    fn default() -> Self {
+        default_serde_instance().expect("default instance")
+    }
+}
+
+// Recreated whenever the config changes (mt, sugoi_url, http_proxy)
+static CLIENT: Mutex<Option<(MtConfig, Option<String>, Option<String>, Arc<dyn MtClient>)>> = Mutex::new(None);
+
+pub fn client() -> Arc<dyn MtClient> {
+    let config = Hachimi::instance().config.load();
+    let mut client = CLIENT.lock().unwrap();
+    if let Some((mt_config, sugoi_url, http_proxy, client)) = client.as_ref() {
+        if *mt_config == config.mt && *sugoi_url == config.sugoi_url && *http_proxy == config.http_proxy {
+            return client.clone();
+        }
+    }
+
+    let new_client = config.mt.create_client(config.sugoi_url.as_ref());
+    *client = Some((config.mt.clone(), config.sugoi_url.clone(), config.http_proxy.clone(), new_client.clone()));
+    new_client
+}
+
+/// Translates the strings through the cache, only the ones that aren't cached are sent to the backend.
+/// `names` are the TextId names of the strings (if they have one), it can be shorter than `content`.
+fn translate_ex(content: &[String], names: &[Option<&str>]) -> Result<Vec<String>, Error> {
+    let hachimi_config = Hachimi::instance().config.load();
+    let config = &hachimi_config.mt;
+    let get_name = |i: usize| names.get(i).copied().flatten();
+    let mut results: Vec<Option<String>> = content.iter()
+        .enumerate()
+        .map(|(i, source)| cache::get(config, source, get_name(i)))
+        .collect();
+
+    // Glossary terms don't need to be sent at all
+    let localized_data = Hachimi::instance().localized_data.load();
+    let glossary = &localized_data.glossary;
+    for (source, result) in content.iter().zip(results.iter_mut()) {
+        if result.is_none() {
+            *result = glossary.get(source).map(|s| s.to_owned());
+        }
+    }
+
+    let mut replacements = Vec::new();
+    let misses: Vec<String> = content.iter()
+        .zip(results.iter())
+        .filter(|(_, result)| result.is_none())
+        .map(|(source, _)| {
+            let (protected, term_translations) = glossary.protect(source);
+            replacements.push(term_translations);
+            protected
+        })
+        .collect();
+    if !misses.is_empty() {
+        let translated = client().translate(&misses)?;
+        if translated.len() != misses.len() {
+            return Err(Error::RuntimeError("Server returned invalid amount of translated content".to_owned()));
+        }
+
+        let mut translated_iter = translated.into_iter().zip(replacements.iter());
+        for (i, (source, result)) in content.iter().zip(results.iter_mut()).enumerate() {
+            if result.is_none() {
+                let (text, term_translations) = translated_iter.next().unwrap();
+                let text = Glossary::restore(&text, term_translations);
+                cache::insert(config, source, &text, get_name(i));
+                *result = Some(text);
+            }
+        }
+    }
+
+    Ok(results.into_iter().map(|result| result.unwrap()).collect())
+}
+
+pub fn translate(content: &[String]) -> Result<Vec<String>, Error> {
+    translate_ex(content, &[])
+}
+
+pub fn translate_one(content: String, name: Option<&str>) -> Result<String, Error> {
+    let mut res = translate_ex(&[content], &[name])?;
+    Ok(res.pop().unwrap())
+}
+
+/// Returns the cached translation without making any requests.
+pub fn get_cached(content: &str, name: Option<&str>) -> Option<String> {
+    cache::get(&Hachimi::instance().config.load().mt, content, name)
+}
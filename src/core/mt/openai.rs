@@ -0,0 +1,124 @@
+use serde::{Deserialize, Serialize};
+
+use crate::core::{http, Error};
+
+use super::{MtClient, MtConfig};
+
+// %{source} and %{target} are replaced with the configured languages
+const DEFAULT_SYSTEM_PROMPT: &str = "You are a translator for a Japanese mobile game. \
+    Translate each string in the JSON array that the user sends from %{source} to %{target}. \
+    Keep any markup and placeholders as they are. \
+    Reply with only a JSON array of the translated strings, in the same order.";
+
+pub struct OpenAiClient {
+    agent: ureq::Agent,
+    url: String,
+    config: MtConfig
+}
+
+impl OpenAiClient {
+    pub fn new(url: String, config: &MtConfig) -> OpenAiClient {
+        OpenAiClient {
+            agent: http::agent_for(&url),
+            url,
+            config: config.clone()
+        }
+    }
+
+    fn system_prompt(&self) -> String {
+        self.config.system_prompt.as_deref()
+            .unwrap_or(DEFAULT_SYSTEM_PROMPT)
+            .replace("%{source}", &self.config.source_lang)
+            .replace("%{target}", &self.config.target_lang)
+    }
+}
+
+// Models like to wrap their replies in code blocks
+fn strip_code_block(content: &str) -> &str {
+    let content = content.trim();
+    let Some(inner) = content.strip_prefix("```").and_then(|s| s.strip_suffix("```")) else {
+        return content;
+    };
+    // Skip the language tag
+    inner.split_once('\n').map(|(_, s)| s).unwrap_or(inner)
+}
+
+impl MtClient for OpenAiClient {
+    fn translate(&self, content: &[String]) -> Result<Vec<String>, Error> {
+        let system_prompt = self.system_prompt();
+        let user_content = serde_json::to_string(content)?;
+        let res: Response = self.config.build_request(&self.agent, &self.url)
+            .send_json(Request {
+                model: self.config.model.as_deref(),
+                messages: &[
+                    Message { role: "system", content: &system_prompt },
+                    Message { role: "user", content: &user_content }
+                ],
+                temperature: 0.0
+            })?
+            .into_json()?;
+
+        let Some(choice) = res.choices.into_iter().next() else {
+            return Err(Error::RuntimeError("Server returned no choices".to_owned()));
+        };
+        Ok(serde_json::from_str(strip_code_block(&choice.message.content))?)
+    }
+}
+
+#[derive(Serialize)]
+struct Request<'a> {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    model: Option<&'a str>,
+    messages: &'a [Message<'a>],
+    temperature: f32
+}
+
+#[derive(Serialize)]
+struct Message<'a> {
+    role: &'a str,
+    content: &'a str
+}
+
+#[derive(Deserialize)]
+struct Response {
+    choices: Vec<Choice>
+}
+
+#[derive(Deserialize)]
+struct Choice {
+    message: ResponseMessage
+}
+
+#[derive(Deserialize)]
+struct ResponseMessage {
+    content: String
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core::http::test_server;
+
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let (url, server) = test_server::spawn_json(1, serde_json::json!({
+            "choices": [
+                { "index": 0, "message": { "role": "assistant", "content": "```json\n[\"Hello\", \"World\"]\n```" } }
+            ]
+        }));
+        let config = MtConfig { model: Some("test-model".to_owned()), ..MtConfig::default() };
+        let client = OpenAiClient { agent: ureq::Agent::new(), url: url + "/v1/chat/completions", config };
+
+        let res = client.translate(&["こんにちは".to_owned(), "世界".to_owned()]).unwrap();
+        assert_eq!(res, ["Hello", "World"]);
+
+        let request = server.join().unwrap().pop().unwrap().json();
+        assert_eq!(request["model"], "test-model");
+        let messages = request["messages"].as_array().unwrap();
+        assert_eq!(messages[0]["role"], "system");
+        assert!(messages[0]["content"].as_str().unwrap().contains("from ja to en"));
+        assert_eq!(messages[1]["role"], "user");
+        assert_eq!(messages[1]["content"], r#"["こんにちは","世界"]"#);
+    }
+}
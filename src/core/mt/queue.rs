@@ -0,0 +1,106 @@
+/*
+    Background auto translation for UI strings, so that slow backends don't stall the game.
+    Untranslated strings are queued (deduplicated, up to MAX_PENDING) and sent in batches by a worker
+    thread; the results go to the MT cache, where the next lookup of the string picks them up.
+*/
+use std::{collections::VecDeque, sync::{Condvar, Mutex}, time::Duration};
+
+use fnv::FnvHashSet;
+use once_cell::sync::Lazy;
+
+const MAX_PENDING: usize = 256;
+const BATCH_SIZE: usize = 32;
+// Wait a bit before trying again if the backend fails
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Default, Clone)]
+pub struct Status {
+    pub pending: usize,
+    pub translated: usize,
+    pub failed: usize,
+    pub last_error: Option<String>
+}
+
+#[derive(Default)]
+struct Queue {
+    // (source, name)
+    pending: VecDeque<(String, Option<String>)>,
+    // Sources that are either pending or being translated
+    queued: FnvHashSet<String>,
+    status: Status
+}
+
+static QUEUE: Lazy<(Mutex<Queue>, Condvar)> = Lazy::new(|| {
+    std::thread::spawn(worker);
+    (Mutex::default(), Condvar::new())
+});
+
+/// Queues the string for translation. Does nothing if it's already queued or the queue is full.
+pub fn enqueue(source: &str, name: Option<&str>) {
+    let (queue, cvar) = &*QUEUE;
+    let mut queue = queue.lock().unwrap();
+    if queue.pending.len() >= MAX_PENDING || queue.queued.contains(source) {
+        return;
+    }
+
+    queue.queued.insert(source.to_owned());
+    queue.pending.push_back((source.to_owned(), name.map(|s| s.to_owned())));
+    queue.status.pending = queue.pending.len();
+    cvar.notify_one();
+}
+
+/// Returns the cached translation, or queues the string and returns None.
+pub fn translate_or_enqueue(source: &str, name: Option<&str>) -> Option<String> {
+    if let Some(text) = super::get_cached(source, name) {
+        return Some(text);
+    }
+    enqueue(source, name);
+    None
+}
+
+pub fn status() -> Status {
+    QUEUE.0.lock().unwrap().status.clone()
+}
+
+fn worker() {
+    let (queue, cvar) = &*QUEUE;
+    loop {
+        let batch: Vec<(String, Option<String>)> = {
+            let mut queue = queue.lock().unwrap();
+            while queue.pending.is_empty() {
+                queue = cvar.wait(queue).unwrap();
+            }
+            let len = queue.pending.len().min(BATCH_SIZE);
+            let batch = queue.pending.drain(..len).collect();
+            queue.status.pending = queue.pending.len();
+            batch
+        };
+
+        let sources: Vec<String> = batch.iter().map(|(source, _)| source.clone()).collect();
+        let names: Vec<Option<&str>> = batch.iter().map(|(_, name)| name.as_deref()).collect();
+        let res = super::translate_ex(&sources, &names);
+
+        let failed = {
+            let mut queue = queue.lock().unwrap();
+            for source in sources.iter() {
+                queue.queued.remove(source);
+            }
+            match res {
+                Ok(_) => {
+                    queue.status.translated += sources.len();
+                    false
+                }
+                Err(e) => {
+                    warn!("Auto translation failed: {}", e);
+                    queue.status.failed += sources.len();
+                    queue.status.last_error = Some(e.to_string());
+                    true
+                }
+            }
+        };
+
+        if failed {
+            std::thread::sleep(RETRY_DELAY);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use serde::Serialize;
+
+use crate::core::{http, Error};
+
+use super::{MtClient, MtConfig};
+
+pub struct SugoiClient {
+    agent: ureq::Agent,
+    url: String,
+    config: MtConfig
+}
+
+impl SugoiClient {
+    pub fn new(url: String, config: &MtConfig) -> SugoiClient {
+        SugoiClient {
+            agent: http::agent_for(&url),
+            url,
+            config: config.clone()
+        }
+    }
+}
+
+impl MtClient for SugoiClient {
+    fn translate(&self, content: &[String]) -> Result<Vec<String>, Error> {
+        Ok(self.config.build_request(&self.agent, &self.url)
+            .send_json(Message::TranslateSentences { content })?
+            .into_json()?
+        )
+    }
+}
+
+#[derive(Serialize)]
+#[serde(tag = "message")]
+enum Message<'a> {
+    #[serde(rename = "translate sentences")]
+    TranslateSentences {
+        content: &'a [String]
+    }
+}
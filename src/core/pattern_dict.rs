@@ -0,0 +1,142 @@
+/*
+    Pattern based translations for text that can't be matched exactly (numbers, names, dates...)
+    [
+        { "pattern": "残り(\\d+)ターン", "text": "$(plural ${1} '$ turn left' '$ turns left')" },
+        { "pattern": "(?<name>.+)のトレーニング", "text": "${name}'s training" }
+    ]
+    Patterns must match the whole text and are tried in order. The text is a template where the
+    capture groups are available as variables (${1}, ${name}...).
+*/
+use regex::{Captures, Regex, RegexSet};
+use serde::{Deserialize, Serialize};
+
+use super::template;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PatternEntry {
+    pub pattern: String,
+    pub text: String
+}
+
+struct Compiled {
+    set: RegexSet,
+    // Same order as the set
+    patterns: Vec<(Regex, String)>
+}
+
+#[derive(Serialize, Deserialize, Default)]
+#[serde(transparent)]
+pub struct PatternDict {
+    entries: Vec<PatternEntry>,
+    #[serde(skip)]
+    compiled: Option<Compiled>
+}
+
+impl PatternDict {
+    /// Compiles the patterns, invalid ones are skipped.
+    /// Must be called after the dict has been fully loaded/merged.
+    pub fn compile(&mut self) {
+        let mut patterns = Vec::with_capacity(self.entries.len());
+        for entry in self.entries.iter() {
+            match Regex::new(&format!("^(?:{})$", entry.pattern)) {
+                Ok(regex) => patterns.push((regex, entry.text.clone())),
+                Err(e) => error!("Invalid pattern '{}': {}", entry.pattern, e)
+            }
+        }
+
+        let set = match RegexSet::new(patterns.iter().map(|(regex, _)| regex.as_str())) {
+            Ok(v) => v,
+            Err(e) => {
+                error!("Failed to compile patterns: {}", e);
+                return;
+            }
+        };
+        self.compiled = Some(Compiled { set, patterns });
+    }
+
+    /// Puts the other dict's patterns in front of this one's (so they take priority).
+    pub fn prepend(&mut self, mut other: PatternDict) {
+        other.entries.append(&mut self.entries);
+        self.entries = other.entries;
+        self.compiled = None;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.compiled.as_ref().map(|c| c.patterns.is_empty()).unwrap_or(true)
+    }
+
+    pub fn len(&self) -> usize {
+        self.compiled.as_ref().map(|c| c.patterns.len()).unwrap_or(0)
+    }
+
+    /// Returns the text template of the first pattern that matches, along with its captures.
+    pub fn find<'a, 'h>(&'a self, text: &'h str) -> Option<(&'a str, Captures<'h>)> {
+        let compiled = self.compiled.as_ref()?;
+        let i = compiled.set.matches(text).iter().next()?;
+        let (regex, template) = &compiled.patterns[i];
+        Some((template, regex.captures(text)?))
+    }
+
+    pub fn patterns(&self) -> impl Iterator<Item = &(Regex, String)> {
+        self.compiled.iter().flat_map(|c| c.patterns.iter())
+    }
+}
+
+/// Template variable lookup for the captures of a match: ${1}, ${name}
+pub fn capture_var(captures: &Captures, name: &str) -> Option<template::Token> {
+    let capture = if let Ok(i) = name.parse::<usize>() {
+        captures.get(i)
+    }
+    else {
+        captures.name(name)
+    };
+    Some(template::Token::StringLit(capture?.as_str().to_owned()))
+}
+
+/// Names of the variables that capture_var would provide for the regex.
+pub fn capture_var_names(regex: &Regex) -> Vec<String> {
+    regex.capture_names()
+        .enumerate()
+        .flat_map(|(i, name)| std::iter::once(i.to_string()).chain(name.map(|s| s.to_owned())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestContext<'a>(&'a Captures<'a>);
+
+    impl template::Context for TestContext<'_> {
+        fn on_filter_eval(&mut self, name: &str, args: &[template::Token]) -> Option<String> {
+            match (name, args.first()?) {
+                ("double", template::Token::NumberLit(n)) => Some((n * 2.0).to_string()),
+                _ => None
+            }
+        }
+
+        fn on_var_eval(&mut self, name: &str) -> Option<template::Token> {
+            capture_var(self.0, name)
+        }
+    }
+
+    fn apply(entries: serde_json::Value, text: &str) -> Option<String> {
+        let mut dict: PatternDict = serde_json::from_value(entries).unwrap();
+        dict.compile();
+        let (template, captures) = dict.find(text)?;
+        Some(template::Parser::new(&[]).eval_with_context(template, &mut TestContext(&captures)))
+    }
+
+    #[test]
+    fn captures() {
+        let entries = serde_json::json!([
+            { "pattern": "No\\.(\\d+)", "text": "#${1}" },
+            { "pattern": "(?<count>\\d+)x", "text": "$(double ${count})" },
+            { "pattern": ".+", "text": "fallback" }
+        ]);
+        assert_eq!(apply(entries.clone(), "No.007").as_deref(), Some("#007"));
+        assert_eq!(apply(entries.clone(), "012x").as_deref(), Some("24"));
+        assert_eq!(apply(entries.clone(), "No.007 ").as_deref(), Some("fallback"));
+        assert_eq!(apply(entries, "").as_deref(), None);
+    }
+}
@@ -1,7 +1,9 @@
 /*
     gettext plural forms expression parser / resolver
     Modified from https://github.com/justinas/gettext
-    Limitation: It has no concept of operator precedence, plz use parentheses
+    Follows C operator precedence, so Plural-Forms headers can be used as-is, e.g.
+    "nplurals=3; plural=n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<10 || n%100>=20) ? 1 : 2;"
+    Named CLDR rules can also be used instead of an expression, e.g. "cldr:ru" (see CLDR_PLURAL_RULES)
 
     The MIT License (MIT)
 
@@ -44,35 +46,86 @@ impl Default for Resolver {
     }
 }
 
-/// Finds the index of a pattern, outside of parenthesis
-fn index_of(src: &str, pat: &str) -> Option<usize> {
-    src.chars()
-        .fold(
-            (None, 0, 0, 0),
-            |(match_index, i, n_matches, paren_level), ch| {
-                if let Some(x) = match_index {
-                    (Some(x), i, n_matches, paren_level)
-                } else {
-                    let new_par_lvl = match ch {
-                        '(' => paren_level + 1,
-                        ')' => paren_level - 1,
-                        _ => paren_level,
-                    };
-
-                    if Some(ch) == pat.chars().nth(n_matches) {
-                        let length = n_matches + 1;
-                        if length == pat.len() && new_par_lvl == 0 {
-                            (Some(i - n_matches), i + 1, length, new_par_lvl)
-                        } else {
-                            (match_index, i + 1, length, new_par_lvl)
-                        }
-                    } else {
-                        (match_index, i + 1, 0, new_par_lvl)
-                    }
-                }
-            },
-        )
-        .0
+const CLDR_PREFIX: &str = "cldr:";
+
+// Built-in rules for the "cldr:xx" presets, written as gettext expressions.
+// Only integer rules are needed. The forms are ordered like the CLDR categories
+// (zero, one, two, few, many, other), skipping the ones that the locale doesn't use.
+static CLDR_PLURAL_RULES: &[(&[&str], &str)] = &[
+    // other
+    (&["ja", "zh", "ko", "th", "vi", "id", "ms", "lo", "my", "km"], "0"),
+    // one, other
+    (&[
+        "en", "de", "nl", "sv", "da", "no", "nb", "nn", "fi", "et", "it", "es", "pt_PT", "pt-PT", "el", "hu", "tr",
+        "bg", "ca", "eu", "gl", "af", "sq", "az", "ka", "kk", "ky", "mn", "ur", "sw", "ta", "te", "ml",
+        "bn", "ne", "eo", "fy"
+    ], "n != 1"),
+    (&["fr", "pt", "hi", "fa", "hy", "zu", "am", "kn", "gu", "mr"], "n > 1"),
+    // one, few, many
+    (&["ru", "uk", "be"], "n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<12 || n%100>14) ? 1 : 2"),
+    (&["pl"], "n==1 ? 0 : n%10>=2 && n%10<=4 && (n%100<12 || n%100>14) ? 1 : 2"),
+    // one, few, other
+    (&["lt"], "n%10==1 && (n%100<11 || n%100>19) ? 0 : n%10>=2 && (n%100<11 || n%100>19) ? 1 : 2"),
+    (&["hr", "sr", "bs"], "n%10==1 && n%100!=11 ? 0 : n%10>=2 && n%10<=4 && (n%100<12 || n%100>14) ? 1 : 2"),
+    (&["cs", "sk"], "n==1 ? 0 : n>=2 && n<=4 ? 1 : 2"),
+    (&["ro", "mo"], "n==1 ? 0 : n==0 || (n%100>=1 && n%100<=19) ? 1 : 2"),
+    // zero, one, other
+    (&["lv"], "n%10==0 || (n%100>=11 && n%100<=19) ? 0 : n%10==1 && n%100!=11 ? 1 : 2"),
+    // one, two, other
+    (&["he", "iw"], "n==1 ? 0 : n==2 ? 1 : 2"),
+    // one, two, few, other
+    (&["sl"], "n%100==1 ? 0 : n%100==2 ? 1 : n%100==3 || n%100==4 ? 2 : 3"),
+    // one, two, few, many, other
+    (&["ga"], "n==1 ? 0 : n==2 ? 1 : n>=3 && n<=6 ? 2 : n>=7 && n<=10 ? 3 : 4"),
+    // zero, one, two, few, many, other
+    (&["ar"], "n==0 ? 0 : n==1 ? 1 : n==2 ? 2 : n%100>=3 && n%100<=10 ? 3 : n%100>=11 ? 4 : 5"),
+    (&["cy"], "n==0 ? 0 : n==1 ? 1 : n==2 ? 2 : n==3 ? 3 : n==6 ? 4 : 5")
+];
+
+// Same as above, for ordinals. Locales that aren't listed only have "other".
+static CLDR_ORDINAL_RULES: &[(&[&str], &str)] = &[
+    // one, two, few, other
+    (&["en"], "n%10==1 && n%100!=11 ? 0 : n%10==2 && n%100!=12 ? 1 : n%10==3 && n%100!=13 ? 2 : 3"),
+    (&["ca"], "n==1 || n==3 ? 0 : n==2 ? 1 : n==4 ? 2 : 3"),
+    // one, other
+    (&["fr", "ms", "vi", "ro", "ga", "hy"], "n==1 ? 0 : 1"),
+    (&["sv"], "(n%10==1 || n%10==2) && n%100!=11 && n%100!=12 ? 0 : 1"),
+    (&["hu"], "n==1 || n==5 ? 0 : 1"),
+    // many, other
+    (&["it"], "n==11 || n==8 || n==80 || n==800 ? 0 : 1")
+];
+
+fn find_cldr_rule(rules: &[(&[&str], &'static str)], locale: &str) -> Option<&'static str> {
+    let find = |locale: &str| rules.iter()
+        .find(|(locales, _)| locales.iter().any(|l| l.eq_ignore_ascii_case(locale)))
+        .map(|(_, rule)| *rule);
+
+    // Try the full locale first (pt_PT), then the language only (pt)
+    find(locale).or_else(|| find(locale.split(['_', '-']).next()?))
+}
+
+/// Builds the value of a PO Plural-Forms header from a `plural_form` value.
+pub fn plural_forms_header(plural_form: &str) -> Result<String, Error> {
+    let plural_form = plural_form.trim();
+    let expr = if let Some(locale) = plural_form.strip_prefix(CLDR_PREFIX) {
+        find_cldr_rule(CLDR_PLURAL_RULES, locale).ok_or_else(|| Error::UnknownPluralLocale(locale.to_owned()))?
+    }
+    else {
+        strip_header(plural_form)
+    };
+
+    // There's no nice way to get the number of forms from an expression, just try a bunch of numbers
+    let ast = Ast::parse(expr)?;
+    let count = (0..1000).map(|n| ast.resolve(n)).max().unwrap_or(0) + 1;
+    Ok(format!("nplurals={}; plural={};", count, expr))
+}
+
+fn strip_header(src: &str) -> &str {
+    let mut src = src.trim();
+    if let Some(i) = src.find("plural=") {
+        src = &src[i + "plural=".len()..];
+    }
+    src.trim_end().trim_end_matches(';')
 }
 
 use self::Ast::*;
@@ -93,7 +146,7 @@ pub enum Ast {
     Not(Box<Ast>),
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Operator {
     Equal,
     NotEqual,
@@ -110,6 +163,151 @@ pub enum Operator {
     Modulo
 }
 
+impl Operator {
+    fn from_str(s: &str) -> Option<Operator> {
+        Some(match s {
+            "==" => Operator::Equal,
+            "!=" => Operator::NotEqual,
+            ">=" => Operator::GreaterOrEqual,
+            "<=" => Operator::SmallerOrEqual,
+            ">" => Operator::Greater,
+            "<" => Operator::Smaller,
+            "&&" => Operator::And,
+            "||" => Operator::Or,
+            "+" => Operator::Plus,
+            "-" => Operator::Minus,
+            "/" => Operator::Divide,
+            "*" => Operator::Multiply,
+            "%" => Operator::Modulo,
+            _ => return None
+        })
+    }
+
+    // Higher binds tighter, same as C
+    fn precedence(self) -> u8 {
+        match self {
+            Operator::Or => 1,
+            Operator::And => 2,
+            Operator::Equal | Operator::NotEqual => 3,
+            Operator::GreaterOrEqual | Operator::SmallerOrEqual | Operator::Greater | Operator::Smaller => 4,
+            Operator::Plus | Operator::Minus => 5,
+            Operator::Divide | Operator::Multiply | Operator::Modulo => 6
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Token<'a> {
+    N,
+    Integer(u64),
+    Op(&'a str),
+    Not,
+    Question,
+    Colon,
+    LParen,
+    RParen
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token<'_>>, Error> {
+    let bytes = src.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let c = bytes[i];
+        let next = bytes.get(i + 1).copied();
+        let (token, len) = match c {
+            b' ' | b'\t' | b'\r' | b'\n' => {
+                i += 1;
+                continue;
+            }
+            b'0'..=b'9' => {
+                let len = bytes[i..].iter().take_while(|c| c.is_ascii_digit()).count();
+                let value = src[i..i + len].parse().map_err(|_| Error::PluralParsing)?;
+                (Token::Integer(value), len)
+            }
+            b'n' => (Token::N, 1),
+            b'(' => (Token::LParen, 1),
+            b')' => (Token::RParen, 1),
+            b'?' => (Token::Question, 1),
+            b':' => (Token::Colon, 1),
+            b'=' | b'!' | b'>' | b'<' if next == Some(b'=') => (Token::Op(&src[i..i + 2]), 2),
+            b'&' | b'|' if next == Some(c) => (Token::Op(&src[i..i + 2]), 2),
+            b'!' => (Token::Not, 1),
+            b'>' | b'<' | b'+' | b'-' | b'/' | b'*' | b'%' => (Token::Op(&src[i..i + 1]), 1),
+            _ => return Err(Error::PluralParsing)
+        };
+        tokens.push(token);
+        i += len;
+    }
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), Error> {
+        if self.peek() != Some(token) {
+            return Err(Error::PluralParsing);
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    // cond ? a : b (right associative, lowest precedence)
+    fn parse_ternary(&mut self) -> Result<Ast, Error> {
+        let cond = self.parse_binary(1)?;
+        if self.peek() != Some(Token::Question) {
+            return Ok(cond);
+        }
+        self.pos += 1;
+
+        let ok = self.parse_ternary()?;
+        self.expect(Token::Colon)?;
+        let nok = self.parse_ternary()?;
+        Ok(Ternary(Box::new(cond), Box::new(ok), Box::new(nok)))
+    }
+
+    // Precedence climbing, binary operators are all left associative
+    fn parse_binary(&mut self, min_precedence: u8) -> Result<Ast, Error> {
+        let mut lhs = self.parse_unary()?;
+        while let Some(Token::Op(op_str)) = self.peek() {
+            let op = Operator::from_str(op_str).ok_or(Error::PluralParsing)?;
+            let precedence = op.precedence();
+            if precedence < min_precedence {
+                break;
+            }
+            self.pos += 1;
+
+            let rhs = self.parse_binary(precedence + 1)?;
+            lhs = Op(op, Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Ast, Error> {
+        let token = self.peek().ok_or(Error::PluralParsing)?;
+        self.pos += 1;
+        match token {
+            Token::Not => Ok(Not(Box::new(self.parse_unary()?))),
+            Token::N => Ok(N),
+            Token::Integer(x) => Ok(Integer(x)),
+            Token::LParen => {
+                let ast = self.parse_ternary()?;
+                self.expect(Token::RParen)?;
+                Ok(ast)
+            }
+            _ => Err(Error::PluralParsing)
+        }
+    }
+}
+
 impl Ast {
     fn resolve(&self, n: u64) -> usize {
         match *self {
@@ -131,11 +329,11 @@ impl Ast {
                 Operator::Smaller => (lhs.resolve(n) < rhs.resolve(n)) as usize,
                 Operator::And => (lhs.resolve(n) != 0 && rhs.resolve(n) != 0) as usize,
                 Operator::Or => (lhs.resolve(n) != 0 || rhs.resolve(n) != 0) as usize,
-                Operator::Plus => lhs.resolve(n) + rhs.resolve(n),
-                Operator::Minus => lhs.resolve(n) - rhs.resolve(n),
-                Operator::Divide => lhs.resolve(n) / rhs.resolve(n),
-                Operator::Multiply => lhs.resolve(n) * rhs.resolve(n),
-                Operator::Modulo => lhs.resolve(n) % rhs.resolve(n),
+                Operator::Plus => lhs.resolve(n).wrapping_add(rhs.resolve(n)),
+                Operator::Minus => lhs.resolve(n).wrapping_sub(rhs.resolve(n)),
+                Operator::Divide => lhs.resolve(n).checked_div(rhs.resolve(n)).unwrap_or(0),
+                Operator::Multiply => lhs.resolve(n).wrapping_mul(rhs.resolve(n)),
+                Operator::Modulo => lhs.resolve(n).checked_rem(rhs.resolve(n)).unwrap_or(0),
             },
             Not(ref val) => match val.resolve(n) {
                 0 => 1,
@@ -144,236 +342,105 @@ impl Ast {
         }
     }
 
+    /// Parses a plural expression. A full Plural-Forms header value
+    /// ("nplurals=2; plural=n != 1;") is also accepted.
     pub fn parse(src: &str) -> Result<Ast, Error> {
-        Self::parse_parens(src.trim())
-    }
-
-    fn parse_parens(src: &str) -> Result<Ast, Error> {
-        if src.starts_with('(') {
-            let end = src[1..src.len() - 1]
-                .chars()
-                .fold((1, 2), |(level, index), ch| match (level, ch) {
-                    (0, '(') => (level + 1, index + 1),
-                    (0, _) => (level, index),
-                    (_, '(') => (level + 1, index + 1),
-                    (_, ')') => (level - 1, index + 1),
-                    (_, _) => (level, index + 1),
-                })
-                .1;
-            if end == src.len() {
-                Ast::parse(src[1..src.len() - 1].trim())
-            } else {
-                Ast::parse_and(src.trim())
-            }
-        } else {
-            Ast::parse_and(src.trim())
-        }
-    }
-
-    fn parse_and(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "&&") {
-            Ok(Ast::Op(
-                Operator::And,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 2..])?),
-            ))
-        } else {
-            Self::parse_or(src)
-        }
-    }
-
-    fn parse_or(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "||") {
-            Ok(Ast::Op(
-                Operator::Or,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 2..])?),
-            ))
-        } else {
-            Self::parse_ternary(src)
-        }
-    }
-
-    fn parse_ternary(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "?") {
-            if let Some(l) = index_of(src, ":") {
-                Ok(Ast::Ternary(
-                    Box::new(Ast::parse(&src[0..i])?),
-                    Box::new(Ast::parse(&src[i + 1..l])?),
-                    Box::new(Ast::parse(&src[l + 1..])?),
-                ))
-            } else {
-                Err(Error::PluralParsing)
-            }
-        } else {
-            Self::parse_ge(src)
-        }
-    }
-
-    fn parse_ge(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, ">=") {
-            Ok(Ast::Op(
-                Operator::GreaterOrEqual,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 2..])?),
-            ))
-        } else {
-            Self::parse_gt(src)
-        }
-    }
-
-    fn parse_gt(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, ">") {
-            Ok(Ast::Op(
-                Operator::Greater,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 1..])?),
-            ))
-        } else {
-            Self::parse_le(src)
-        }
-    }
-
-    fn parse_le(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "<=") {
-            Ok(Ast::Op(
-                Operator::SmallerOrEqual,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 2..])?),
-            ))
-        } else {
-            Self::parse_lt(src)
-        }
-    }
-
-    fn parse_lt(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "<") {
-            Ok(Ast::Op(
-                Operator::Smaller,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 1..])?),
-            ))
-        } else {
-            Self::parse_eq(src)
-        }
-    }
-
-    fn parse_eq(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "==") {
-            Ok(Ast::Op(
-                Operator::Equal,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 2..])?),
-            ))
-        } else {
-            Self::parse_neq(src)
-        }
-    }
-
-    fn parse_neq(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "!=") {
-            Ok(Ast::Op(
-                Operator::NotEqual,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 2..])?),
-            ))
-        } else {
-            Self::parse_plus(src)
+        let mut parser = Parser { tokens: tokenize(strip_header(src))?, pos: 0 };
+        let ast = parser.parse_ternary()?;
+        if parser.pos != parser.tokens.len() {
+            return Err(Error::PluralParsing);
         }
+        Ok(ast)
     }
+}
 
-    fn parse_plus(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "+") {
-            Ok(Ast::Op(
-                Operator::Plus,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 1..])?),
-            ))
-        } else {
-            Self::parse_minus(src.trim())
+impl Resolver {
+    /// Returns the number of the correct plural form
+    /// for `n` objects, as defined by the rule contained in this resolver.
+    pub fn resolve(&self, n: u64) -> usize {
+        match *self {
+            Expr(ref ast) => ast.resolve(n),
+            Function(ref f) => f(n),
         }
     }
 
-    fn parse_minus(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "-") {
-            Ok(Ast::Op(
-                Operator::Minus,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 1..])?),
-            ))
-        } else {
-            Self::parse_divide(src.trim())
-        }
+    /// Parses a `plural_form` value, either an expression or a CLDR preset ("cldr:ru").
+    pub fn parse_plural_form(src: &str) -> Result<Resolver, Error> {
+        Self::parse_with_rules(src, CLDR_PLURAL_RULES, false)
     }
 
-    fn parse_divide(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "/") {
-            Ok(Ast::Op(
-                Operator::Divide,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 1..])?),
-            ))
-        } else {
-            Self::parse_multiply(src.trim())
-        }
+    /// Parses an `ordinal_form` value, either an expression or a CLDR preset ("cldr:en").
+    pub fn parse_ordinal_form(src: &str) -> Result<Resolver, Error> {
+        Self::parse_with_rules(src, CLDR_ORDINAL_RULES, true)
     }
 
-    fn parse_multiply(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "*") {
-            Ok(Ast::Op(
-                Operator::Multiply,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 1..])?),
-            ))
-        } else {
-            Self::parse_mod(src.trim())
-        }
-    }
+    fn parse_with_rules(src: &str, rules: &[(&[&str], &'static str)], other_only_fallback: bool) -> Result<Resolver, Error> {
+        let src = src.trim();
+        let Some(locale) = src.strip_prefix(CLDR_PREFIX) else {
+            return Ok(Expr(Ast::parse(src)?));
+        };
 
-    fn parse_mod(src: &str) -> Result<Ast, Error> {
-        if let Some(i) = index_of(src, "%") {
-            Ok(Ast::Op(
-                Operator::Modulo,
-                Box::new(Ast::parse(&src[0..i])?),
-                Box::new(Ast::parse(&src[i + 1..])?),
-            ))
-        } else {
-            Self::parse_not(src.trim())
+        if let Some(rule) = find_cldr_rule(rules, locale) {
+            return Ok(Expr(Ast::parse(rule)?));
         }
-    }
-
-    fn parse_not(src: &str) -> Result<Ast, Error> {
-        if index_of(src, "!") == Some(0) {
-            Ok(Ast::Not(Box::new(Ast::parse(&src[1..])?)))
-        } else {
-            Self::parse_int(src.trim())
+        // Most locales only have the "other" category for ordinals, but we still
+        // want to catch typos, so it has to be a locale that we know of.
+        if other_only_fallback && find_cldr_rule(CLDR_PLURAL_RULES, locale).is_some() {
+            return Ok(Function(|_| 0));
         }
+        Err(Error::UnknownPluralLocale(locale.to_owned()))
     }
+}
 
-    fn parse_int(src: &str) -> Result<Ast, Error> {
-        if let Ok(x) = u64::from_str_radix(src, 10) {
-            Ok(Ast::Integer(x))
-        } else {
-            Self::parse_n(src.trim())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLES: [u64; 8] = [0, 1, 2, 5, 11, 21, 101, 111];
+
+    #[test]
+    fn cldr_plural_presets() {
+        // Expected form for each sample, from the CLDR plural rules
+        let table: &[(&str, [usize; 8])] = &[
+            ("ja",    [0, 0, 0, 0, 0, 0, 0, 0]),
+            ("en",    [1, 0, 1, 1, 1, 1, 1, 1]),
+            ("pt_PT", [1, 0, 1, 1, 1, 1, 1, 1]),
+            ("pt",    [0, 0, 1, 1, 1, 1, 1, 1]),
+            ("pt_BR", [0, 0, 1, 1, 1, 1, 1, 1]),
+            ("fr",    [0, 0, 1, 1, 1, 1, 1, 1]),
+            ("ru",    [2, 0, 1, 2, 2, 0, 0, 2]),
+            ("pl",    [2, 0, 1, 2, 2, 2, 2, 2]),
+            ("lt",    [2, 0, 1, 1, 2, 0, 0, 2]),
+            ("hr",    [2, 0, 1, 2, 2, 0, 0, 2]),
+            ("cs",    [2, 0, 1, 2, 2, 2, 2, 2]),
+            ("ro",    [1, 0, 1, 1, 1, 2, 1, 1]),
+            ("lv",    [0, 1, 2, 2, 0, 1, 1, 0]),
+            ("he",    [2, 0, 1, 2, 2, 2, 2, 2]),
+            ("sl",    [3, 0, 1, 3, 3, 3, 0, 3]),
+            ("ga",    [4, 0, 1, 2, 4, 4, 4, 4]),
+            ("ar",    [0, 1, 2, 3, 4, 4, 5, 4]),
+            ("cy",    [0, 1, 2, 5, 5, 5, 5, 5])
+        ];
+
+        for (locale, expected) in table {
+            let resolver = Resolver::parse_plural_form(&format!("cldr:{}", locale)).unwrap();
+            let forms = SAMPLES.map(|n| resolver.resolve(n));
+            assert_eq!(&forms, expected, "cldr:{}", locale);
         }
     }
 
-    fn parse_n(src: &str) -> Result<Ast, Error> {
-        if src == "n" {
-            Ok(Ast::N)
-        } else {
-            Err(Error::PluralParsing)
+    #[test]
+    fn cldr_ordinal_presets() {
+        let table: &[(&str, [usize; 8])] = &[
+            ("en", [3, 0, 1, 3, 3, 0, 0, 3]),
+            ("fr", [1, 0, 1, 1, 1, 1, 1, 1]),
+            ("ja", [0, 0, 0, 0, 0, 0, 0, 0])
+        ];
+
+        for (locale, expected) in table {
+            let resolver = Resolver::parse_ordinal_form(&format!("cldr:{}", locale)).unwrap();
+            let forms = SAMPLES.map(|n| resolver.resolve(n));
+            assert_eq!(&forms, expected, "cldr:{}", locale);
         }
     }
 }
-
-impl Resolver {
-    /// Returns the number of the correct plural form
-    /// for `n` objects, as defined by the rule contained in this resolver.
-    pub fn resolve(&self, n: u64) -> usize {
-        match *self {
-            Expr(ref ast) => ast.resolve(n),
-            Function(ref f) => f(n),
-        }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,422 @@
+/*
+    gettext PO support for the localized data dicts.
+
+    Mapping:
+    - msgctxt is the dict key. Nested dicts use "key.sub_key" (e.g. "category.index" for text_data_dict)
+    - msgid is the original text if it's known, otherwise it's the same as msgctxt
+    - msgstr is the translated text. Entries that are fuzzy or untranslated are skipped.
+
+    Plural entries are converted to a $(plural n 'form0' 'form1' ...) template, so the forms are picked
+    using the localized data's plural_form just like the msgstr[n] forms in gettext. The n argument is
+    stored in an extracted comment ("#. plural-n: {0}") since it can't be represented in PO.
+*/
+use std::{collections::BTreeMap, fmt::Display, fs, path::Path};
+
+use fnv::FnvHashMap;
+use serde_json::{Map, Value};
+
+use super::{localized_data::LocalizedData, plurals, Error};
+
+pub const EXPORT_DIR: &str = "po_export";
+
+const PLURAL_N_COMMENT: &str = "plural-n:";
+const PLURAL_FILTER_PREFIX: &str = "$(plural ";
+
+#[derive(Default, Debug)]
+pub struct Entry {
+    pub context: Option<String>,
+    pub id: String,
+    pub id_plural: Option<String>,
+    pub strs: Vec<String>,
+    pub fuzzy: bool,
+    pub extracted_comments: Vec<String>
+}
+
+impl Entry {
+    fn is_header(&self) -> bool {
+        self.context.is_none() && self.id.is_empty()
+    }
+
+    fn plural_n(&self) -> Option<&str> {
+        self.extracted_comments.iter()
+            .find_map(|c| c.strip_prefix(PLURAL_N_COMMENT))
+            .map(|n| n.trim())
+    }
+
+    /// Creates an entry from a dict entry. `text` is None for untranslated entries.
+    pub fn from_dict_entry(context: String, text: Option<&str>, original: Option<&str>) -> Entry {
+        let id = original.map(|s| s.to_owned()).unwrap_or_else(|| context.clone());
+        let mut entry = Entry {
+            context: Some(context),
+            id,
+            ..Default::default()
+        };
+
+        let Some(text) = text else {
+            entry.strs.push(String::new());
+            return entry;
+        };
+
+        if let Some((n, forms)) = split_plural_template(text) {
+            entry.id_plural = Some(entry.id.clone());
+            entry.strs = forms;
+            entry.extracted_comments.push(format!("{} {}", PLURAL_N_COMMENT, n));
+        }
+        else {
+            entry.strs.push(text.to_owned());
+        }
+        entry
+    }
+
+    /// Returns the text that would be used in the dict, or None if it shouldn't be used.
+    pub fn to_dict_text(&self) -> Option<String> {
+        if self.fuzzy || self.strs.iter().all(|s| s.is_empty()) {
+            return None;
+        }
+
+        if self.id_plural.is_none() {
+            return self.strs.first().cloned();
+        }
+
+        let Some(n) = self.plural_n() else {
+            warn!("PO: Plural entry '{}' is missing the '{}' comment", self.context.as_deref().unwrap_or(&self.id), PLURAL_N_COMMENT);
+            return None;
+        };
+        let forms: Vec<String> = self.strs.iter()
+            .map(|s| format!("'{}'", s.replace('\'', "\\'")))
+            .collect();
+        Some(format!("{}{} {})", PLURAL_FILTER_PREFIX, n, forms.join(" ")))
+    }
+}
+
+// Only handles templates that consist of a single plural filter with string args
+fn split_plural_template(text: &str) -> Option<(&str, Vec<String>)> {
+    let inner = text.strip_prefix(PLURAL_FILTER_PREFIX)?.strip_suffix(')')?;
+    let (n, mut rest) = inner.split_once(' ')?;
+
+    let mut forms = Vec::new();
+    loop {
+        rest = rest.trim_start_matches(' ');
+        if rest.is_empty() {
+            break;
+        }
+        rest = rest.strip_prefix('\'')?;
+
+        let mut form = String::new();
+        let mut chars = rest.char_indices();
+        let end = loop {
+            match chars.next()? {
+                (_, '\\') => match chars.next()? {
+                    (_, '\'') => form.push('\''),
+                    (_, c) => { form.push('\\'); form.push(c); }
+                },
+                (i, '\'') => break i,
+                (_, c) => form.push(c)
+            }
+        };
+        forms.push(form);
+        rest = &rest[end + 1..];
+    }
+
+    if forms.is_empty() {
+        return None;
+    }
+    Some((n, forms))
+}
+
+fn parse_error(line: usize, msg: &str) -> Error {
+    Error::PoParseError(line, msg.to_owned())
+}
+
+fn unescape(line_num: usize, s: &str) -> Result<String, Error> {
+    let s = s.trim();
+    let inner = s.strip_prefix('"').and_then(|s| s.strip_suffix('"'))
+        .ok_or_else(|| parse_error(line_num, "expected a quoted string"))?;
+
+    let mut res = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            res.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => res.push('\n'),
+            Some('t') => res.push('\t'),
+            Some('r') => res.push('\r'),
+            Some(c @ ('"' | '\\')) => res.push(c),
+            _ => return Err(parse_error(line_num, "invalid escape sequence"))
+        }
+    }
+    Ok(res)
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r")
+        .replace('\n', "\\n")
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Field {
+    Context,
+    Id,
+    IdPlural,
+    Str(usize)
+}
+
+pub fn parse(src: &str) -> Result<Vec<Entry>, Error> {
+    let mut entries = Vec::new();
+    let mut entry = Entry::default();
+    let mut field: Option<Field> = None;
+
+    for (i, line) in src.lines().enumerate() {
+        let line_num = i + 1;
+        let line = line.trim();
+
+        if line.is_empty() || line.starts_with("#~") {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            // Comments belong to the next entry
+            if matches!(field, Some(Field::Str(_))) {
+                entries.push(std::mem::take(&mut entry));
+                field = None;
+            }
+            if let Some(flags) = comment.strip_prefix(',') {
+                entry.fuzzy |= flags.split(',').any(|f| f.trim() == "fuzzy");
+            }
+            else if let Some(extracted) = comment.strip_prefix('.') {
+                entry.extracted_comments.push(extracted.trim().to_owned());
+            }
+            continue;
+        }
+
+        if line.starts_with('"') {
+            let value = unescape(line_num, line)?;
+            match field {
+                Some(Field::Context) => entry.context.get_or_insert_with(String::new).push_str(&value),
+                Some(Field::Id) => entry.id.push_str(&value),
+                Some(Field::IdPlural) => entry.id_plural.get_or_insert_with(String::new).push_str(&value),
+                Some(Field::Str(n)) => entry.strs[n].push_str(&value),
+                None => return Err(parse_error(line_num, "unexpected string"))
+            }
+            continue;
+        }
+
+        let (keyword, value) = line.split_once(char::is_whitespace)
+            .ok_or_else(|| parse_error(line_num, "expected a keyword"))?;
+        let value = unescape(line_num, value)?;
+
+        let new_field = match keyword {
+            "msgctxt" => Field::Context,
+            "msgid" => Field::Id,
+            "msgid_plural" => Field::IdPlural,
+            "msgstr" => Field::Str(0),
+            _ => {
+                let n = keyword.strip_prefix("msgstr[")
+                    .and_then(|s| s.strip_suffix(']'))
+                    .and_then(|s| s.parse::<usize>().ok())
+                    .ok_or_else(|| parse_error(line_num, "unknown keyword"))?;
+                Field::Str(n)
+            }
+        };
+
+        // A new msgctxt/msgid after the msgstr starts the next entry
+        if matches!(new_field, Field::Context | Field::Id) && matches!(field, Some(Field::Str(_))) {
+            entries.push(std::mem::take(&mut entry));
+        }
+
+        match new_field {
+            Field::Context => entry.context = Some(value),
+            Field::Id => entry.id = value,
+            Field::IdPlural => entry.id_plural = Some(value),
+            Field::Str(n) => {
+                if n != entry.strs.len() {
+                    return Err(parse_error(line_num, "msgstr index out of order"));
+                }
+                entry.strs.push(value);
+            }
+        }
+        field = Some(new_field);
+    }
+
+    if field.is_some() {
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+/// Converts the entries to the same structure as the JSON dicts.
+pub fn to_dict_value(entries: &[Entry]) -> Result<Value, Error> {
+    let mut dict = Map::new();
+    let mut no_context_count = 0;
+
+    for entry in entries {
+        if entry.is_header() {
+            continue;
+        }
+        let Some(context) = &entry.context else {
+            no_context_count += 1;
+            continue;
+        };
+        let Some(text) = entry.to_dict_text() else {
+            continue;
+        };
+
+        if let Some((key, sub_key)) = context.split_once('.') {
+            let sub_dict = dict.entry(key).or_insert_with(|| Value::Object(Map::new()));
+            let Value::Object(sub_dict) = sub_dict else {
+                return Err(Error::RuntimeError(format!("PO: '{}' is used as both a text and a dict", key)));
+            };
+            sub_dict.insert(sub_key.to_owned(), Value::String(text));
+        }
+        else {
+            dict.insert(context.clone(), Value::String(text));
+        }
+    }
+
+    if no_context_count != 0 {
+        warn!("PO: Skipped {} entries without msgctxt", no_context_count);
+    }
+
+    Ok(Value::Object(dict))
+}
+
+fn write_field(out: &mut String, keyword: &str, value: &str) {
+    // Split multiline strings like gettext does
+    if !value.contains('\n') || (value.ends_with('\n') && value.matches('\n').count() == 1) {
+        out.push_str(&format!("{} \"{}\"\n", keyword, escape(value)));
+        return;
+    }
+
+    out.push_str(&format!("{} \"\"\n", keyword));
+    for line in value.split_inclusive('\n') {
+        out.push_str(&format!("\"{}\"\n", escape(line)));
+    }
+}
+
+pub fn write(entries: &[Entry], plural_forms: &str) -> String {
+    let mut out = String::new();
+    out.push_str("msgid \"\"\nmsgstr \"\"\n");
+    out.push_str("\"Content-Type: text/plain; charset=UTF-8\\n\"\n");
+    out.push_str(&format!("\"Plural-Forms: {}\\n\"\n", escape(plural_forms)));
+    out.push_str("\"X-Generator: Hachimi\\n\"\n");
+
+    for entry in entries {
+        out.push('\n');
+        for comment in entry.extracted_comments.iter() {
+            out.push_str(&format!("#. {}\n", comment));
+        }
+        if entry.fuzzy {
+            out.push_str("#, fuzzy\n");
+        }
+        if let Some(context) = &entry.context {
+            write_field(&mut out, "msgctxt", context);
+        }
+        write_field(&mut out, "msgid", &entry.id);
+
+        if let Some(id_plural) = &entry.id_plural {
+            write_field(&mut out, "msgid_plural", id_plural);
+            for (i, s) in entry.strs.iter().enumerate() {
+                write_field(&mut out, &format!("msgstr[{}]", i), s);
+            }
+        }
+        else {
+            write_field(&mut out, "msgstr", entry.strs.first().map(|s| s.as_str()).unwrap_or_default());
+        }
+    }
+
+    out
+}
+
+// Numeric keys are sorted as numbers
+fn sort_entries(entries: &mut [Entry]) {
+    entries.sort_by_cached_key(|e| {
+        e.context.as_deref().unwrap_or_default()
+            .split('.')
+            .map(|part| part.parse::<i64>().map_err(|_| part.to_owned()))
+            .collect::<Vec<_>>()
+    });
+}
+
+fn flat_dict_entries<K: Display>(dict: &FnvHashMap<K, String>) -> Vec<Entry> {
+    dict.iter()
+        .map(|(key, text)| Entry::from_dict_entry(key.to_string(), Some(text), None))
+        .collect()
+}
+
+fn nested_dict_entries<K: Display, K2: Display>(dict: &FnvHashMap<K, FnvHashMap<K2, String>>) -> Vec<Entry> {
+    dict.iter()
+        .flat_map(|(key, sub_dict)| sub_dict.iter()
+            .map(move |(sub_key, text)| Entry::from_dict_entry(format!("{}.{}", key, sub_key), Some(text), None))
+        )
+        .collect()
+}
+
+/// Exports the loaded dicts to `out_dir`, one PO file per dict (named after the dict's file).
+/// The localize strings (from Localize::dump_strings) are used as the localize_dict's originals,
+/// and are also exported on their own as a template (localize_dump.pot).
+/// Returns the number of files written.
+pub fn export_dicts(localized_data: &LocalizedData, localize_strings: &BTreeMap<String, String>, out_dir: &Path) -> Result<usize, Error> {
+    let config = &localized_data.config;
+    let plural_forms = match &config.plural_form {
+        Some(plural_form) => plurals::plural_forms_header(plural_form)?,
+        None => "nplurals=1; plural=0;".to_owned()
+    };
+
+    fs::create_dir_all(out_dir)?;
+    let mut count = 0;
+
+    if !localize_strings.is_empty() {
+        let entries: Vec<Entry> = localize_strings.iter()
+            .map(|(name, text)| Entry::from_dict_entry(name.clone(), None, Some(text)))
+            .collect();
+        fs::write(out_dir.join("localize_dump.pot"), write(&entries, &plural_forms))?;
+        count += 1;
+    }
+
+    let localize_entries = {
+        let mut entries: Vec<Entry> = localize_strings.iter()
+            .map(|(name, original)| Entry::from_dict_entry(
+                name.clone(), localized_data.localize_dict.get(name).map(|s| s.as_str()), Some(original)
+            ))
+            .collect();
+        // Keep the entries that aren't in the game (anymore)
+        entries.extend(localized_data.localize_dict.iter()
+            .filter(|(name, _)| !localize_strings.contains_key(*name))
+            .map(|(name, text)| Entry::from_dict_entry(name.clone(), Some(text), None))
+        );
+        entries
+    };
+
+    let mut dicts = vec![
+        (config.localize_dict.as_ref(), localize_entries),
+        (config.text_data_dict.as_ref(), nested_dict_entries(&localized_data.text_data_dict)),
+        (config.character_system_text_dict.as_ref(), nested_dict_entries(&localized_data.character_system_text_dict)),
+        (config.race_jikkyo_comment_dict.as_ref(), flat_dict_entries(&localized_data.race_jikkyo_comment_dict)),
+        (config.race_jikkyo_message_dict.as_ref(), flat_dict_entries(&localized_data.race_jikkyo_message_dict))
+    ];
+    for table in config.sql_tables.iter() {
+        if let Some(dict) = localized_data.sql_table_dicts.get(&table.table) {
+            dicts.push((Some(&table.dict), flat_dict_entries(dict)));
+        }
+    }
+
+    for (dict_path, mut entries) in dicts {
+        let Some(file_name) = dict_path.and_then(|p| Path::new(p).file_stem()) else {
+            continue;
+        };
+
+        sort_entries(&mut entries);
+        let mut path = out_dir.join(file_name);
+        path.set_extension("po");
+        fs::write(path, write(&entries, &plural_forms))?;
+        count += 1;
+    }
+
+    Ok(count)
+}
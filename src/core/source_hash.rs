@@ -0,0 +1,86 @@
+/*
+    Source text fingerprints for the asset dicts (source_hash fields).
+    A fingerprint is the blake3 hash of the original (UTF-8) text in lowercase hex; it can be truncated,
+    but must be at least MIN_LEN characters long. Shorter fingerprints are ignored.
+
+    Translations with a mismatched fingerprint are logged to the stale translation report, and skipped
+    unless apply_stale_translations is enabled.
+*/
+use std::{path::PathBuf, sync::Mutex};
+
+use fnv::FnvHashMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use super::{utils, Error, Hachimi};
+
+pub const REPORT_FILENAME: &str = "stale_report.json";
+pub const MIN_LEN: usize = 8;
+// Length of the fingerprints that we generate
+const DEFAULT_LEN: usize = 16;
+
+static ENTRIES: Lazy<Mutex<FnvHashMap<(String, String), Entry>>> = Lazy::new(|| Mutex::default());
+
+#[derive(Serialize)]
+struct Entry {
+    asset: String,
+    location: String,
+    source_hash: String,
+    current_hash: String,
+    current_text: String
+}
+
+pub fn compute(text: &str) -> String {
+    let mut hash = blake3::hash(text.as_bytes()).to_hex().to_string();
+    hash.truncate(DEFAULT_LEN);
+    hash
+}
+
+fn matches(source_hash: &str, text: &str) -> bool {
+    if source_hash.len() < MIN_LEN {
+        return true;
+    }
+    blake3::hash(text.as_bytes()).to_hex().starts_with(&source_hash.to_ascii_lowercase())
+}
+
+/// Checks the translation's source hash against the current source text.
+/// Returns whether the translation should be applied.
+/// `location` identifies the text inside of the asset (e.g. "text_block_list.3").
+pub fn check(
+    asset: &str, source_hash: Option<&str>, text: impl FnOnce() -> String, location: impl FnOnce() -> String
+) -> bool {
+    let Some(source_hash) = source_hash else {
+        return true;
+    };
+    let text = text();
+    if matches(source_hash, &text) {
+        return true;
+    }
+
+    let location = location();
+    warn!("Stale translation: {} ({})", asset, location);
+    ENTRIES.lock().unwrap().insert((asset.to_owned(), location.clone()), Entry {
+        asset: asset.to_owned(),
+        location,
+        source_hash: source_hash.to_owned(),
+        current_hash: compute(&text),
+        current_text: text
+    });
+
+    Hachimi::instance().config.load().apply_stale_translations
+}
+
+pub fn count() -> usize {
+    ENTRIES.lock().unwrap().len()
+}
+
+/// Writes the report to the data dir. Returns the report's path.
+pub fn save_report() -> Result<PathBuf, Error> {
+    let entries = ENTRIES.lock().unwrap();
+    let mut report: Vec<&Entry> = entries.values().collect();
+    report.sort_by(|a, b| (&a.asset, &a.location).cmp(&(&b.asset, &b.location)));
+
+    let path = Hachimi::instance().get_data_path(REPORT_FILENAME);
+    utils::write_json_file(&report, &path)?;
+    Ok(path)
+}
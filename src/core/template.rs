@@ -4,22 +4,82 @@
     Syntax:
     - Filter: $(filter_name arg1 arg2 arg3 ...)
       Loosely based on Bash command substitution syntax.
-
-    More expression types might be added later, but the filter expression
-    is already suitable for most if not all use cases.
+      Arguments can be numbers, 'strings', identifiers, variables or other filters,
+      e.g. $(plural $(count) '$ item' '$ items')
+    - Variable: ${name}
+      Provided by the context that the template is evaluated with:
+      ${month}/${half} for month_text_format, and the capture groups (${1}, ${name}...)
+      for pattern_dict texts. Other texts have no variables.
+
+    Conditionals are provided by the if and select filters (see template_filters).
 */
+use std::fmt;
+
 use fnv::FnvHashMap;
 
+#[derive(Clone, Debug)]
 pub enum Token {
     Identifier(String),
     NumberLit(f64),
     StringLit(String)
 }
 
+impl Token {
+    /// Converts the result of a nested filter/variable into an argument token.
+    /// Top level results are output as is (so that "007" doesn't become "7").
+    pub fn from_value(value: String) -> Token {
+        let is_number = value.as_bytes().first().is_some_and(|c| c.is_ascii_digit());
+        if is_number {
+            if let Ok(number) = value.parse::<f64>() {
+                return Token::NumberLit(number);
+            }
+        }
+        Token::StringLit(value)
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Identifier(s) | Token::StringLit(s) => write!(f, "{}", s),
+            Token::NumberLit(n) => write!(f, "{}", n)
+        }
+    }
+}
+
 pub type Filter = fn(args: &[Token]) -> Option<String>;
 
 pub trait Context {
     fn on_filter_eval(&mut self, name: &str, args: &[Token]) -> Option<String>;
+
+    fn on_var_eval(&mut self, _name: &str) -> Option<Token> {
+        None
+    }
+
+    fn on_error(&mut self, input: &str, error: ValidationError) {
+        warn!("{} in '{}'", error, input);
+    }
+}
+
+#[derive(Debug)]
+pub enum ValidationError {
+    InvalidToken(usize),
+    UnclosedFilter(usize),
+    UnknownFilter(String),
+    UnknownVariable(String),
+    FilterFailed(String)
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValidationError::InvalidToken(pos) => write!(f, "Invalid token (at pos {})", pos),
+            ValidationError::UnclosedFilter(pos) => write!(f, "Unclosed filter (at pos {})", pos),
+            ValidationError::UnknownFilter(name) => write!(f, "Unknown filter '{}'", name),
+            ValidationError::UnknownVariable(name) => write!(f, "Unknown variable '{}'", name),
+            ValidationError::FilterFailed(name) => write!(f, "Filter '{}' failed to evaluate", name)
+        }
+    }
 }
 
 struct EmptyContext();
@@ -36,6 +96,147 @@ impl Context for FilterRemovalContext {
     fn on_filter_eval(&mut self, _name: &str, _args: &[Token]) -> Option<String> {
         Some(String::new())
     }
+
+    fn on_var_eval(&mut self, _name: &str) -> Option<Token> {
+        Some(Token::StringLit(String::new()))
+    }
+}
+
+enum Expr {
+    Token(Token),
+    Var(String),
+    Filter(String, Vec<Expr>)
+}
+
+enum Part<'a> {
+    Text(&'a str),
+    Expr(Expr, &'a str)
+}
+
+struct ExprParser<'a> {
+    input: &'a str,
+    bytes: &'a [u8],
+    pos: usize
+}
+
+// Iterates through the bytes directly for the sake of simplicity
+// (it's also faster than going through char())
+// A caveat is that the parser has no knowledge of Unicode characters; it doesn't need to anyways,
+// UTF-8 sequences do not conflict with normal ascii characters.
+impl<'a> ExprParser<'a> {
+    fn is_expr_start(&self, pos: usize) -> bool {
+        self.bytes.get(pos) == Some(&b'$') && matches!(self.bytes.get(pos + 1), Some(b'(') | Some(b'{'))
+    }
+
+    fn parse_expr(&mut self) -> Result<Expr, ValidationError> {
+        let start = self.pos;
+        self.pos += 2;
+        if self.bytes[start + 1] == b'(' {
+            self.parse_filter(start)
+        }
+        else {
+            self.parse_var(start)
+        }
+    }
+
+    fn parse_var(&mut self, start: usize) -> Result<Expr, ValidationError> {
+        let name_start = self.pos;
+        while let Some(&c) = self.bytes.get(self.pos) {
+            if c == b'}' {
+                let name = &self.input[name_start..self.pos];
+                self.pos += 1;
+                if name.is_empty() || !name.bytes().all(|c| c.is_ascii_alphanumeric() || c == b'_') {
+                    return Err(ValidationError::InvalidToken(name_start));
+                }
+                return Ok(Expr::Var(name.to_owned()));
+            }
+            self.pos += 1;
+        }
+
+        Err(ValidationError::UnclosedFilter(start))
+    }
+
+    fn parse_filter(&mut self, start: usize) -> Result<Expr, ValidationError> {
+        let mut args = Vec::new();
+        loop {
+            let Some(&c) = self.bytes.get(self.pos) else {
+                return Err(ValidationError::UnclosedFilter(start));
+            };
+
+            match c {
+                b' ' => self.pos += 1,
+
+                b')' => {
+                    self.pos += 1;
+                    break;
+                }
+
+                b'\'' => args.push(Expr::Token(self.parse_string(start)?)),
+
+                _ => if self.is_expr_start(self.pos) {
+                    args.push(self.parse_expr()?);
+                }
+                else {
+                    let token_start = self.pos;
+                    while let Some(&c) = self.bytes.get(self.pos) {
+                        if c == b' ' || c == b')' { break; }
+                        self.pos += 1;
+                    }
+
+                    let token = parse_token(&self.input[token_start..self.pos])
+                        .ok_or(ValidationError::InvalidToken(token_start))?;
+                    args.push(Expr::Token(token));
+                }
+            }
+        }
+
+        let mut iter = args.into_iter();
+        let Some(Expr::Token(Token::Identifier(name))) = iter.next() else {
+            return Err(ValidationError::InvalidToken(start + 2));
+        };
+        Ok(Expr::Filter(name, iter.collect()))
+    }
+
+    fn parse_string(&mut self, start: usize) -> Result<Token, ValidationError> {
+        let string_start = self.pos + 1;
+        self.pos += 1;
+        while let Some(&c) = self.bytes.get(self.pos) {
+            match c {
+                b'\\' => self.pos += 2,
+                b'\'' => {
+                    self.pos += 1;
+                    return Ok(Token::StringLit(self.input[string_start..self.pos - 1].replace("\\'", "'")));
+                }
+                _ => self.pos += 1
+            }
+        }
+
+        Err(ValidationError::UnclosedFilter(start))
+    }
+}
+
+fn parse_token(input: &str) -> Option<Token> {
+    let start_char = input.chars().next()?;
+
+    if start_char.is_numeric() {
+        return if let Ok(number) = input.parse::<f64>() {
+            Some(Token::NumberLit(number))
+        }
+        else if let Ok(number) = input.replace(",", "").parse::<f64>() {
+            // Allow commas
+            // (not doing in the initial parse; the idea being that numbers with commas are not common)
+            Some(Token::NumberLit(number))
+        }
+        else {
+            None
+        }
+    }
+
+    if input.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Some(Token::Identifier(input.to_owned()));
+    }
+
+    None
 }
 
 pub struct Parser {
@@ -52,51 +253,67 @@ impl Parser {
         Parser { filters }
     }
 
-    fn eval_filter(&self, tokens: &Vec<Token>, context: &mut impl Context) -> Option<String> {
-        if tokens.is_empty() { return None; }
-
-        if let Token::Identifier(filter_name) = tokens.first().unwrap() {
-            let args = &tokens.as_slice()[1..];
-            let context_res = context.on_filter_eval(filter_name, args);
-            if context_res.is_some() {
-                return context_res
+    /// Splits the input into plain text and expressions.
+    /// Expressions that fail to parse are kept as plain text.
+    fn parse(input: &str) -> (Vec<Part<'_>>, Vec<ValidationError>) {
+        let mut parts = Vec::new();
+        let mut errors = Vec::new();
+
+        let mut parser = ExprParser { input, bytes: input.as_bytes(), pos: 0 };
+        let mut text_start = 0;
+        while parser.pos < input.len() {
+            if !parser.is_expr_start(parser.pos) {
+                parser.pos += 1;
+                continue;
             }
-            else if let Some(filter) = self.filters.get(filter_name) {
-                return filter(&tokens.as_slice()[1..]);
+
+            let start = parser.pos;
+            match parser.parse_expr() {
+                Ok(expr) => {
+                    parts.push(Part::Text(&input[text_start..start]));
+                    parts.push(Part::Expr(expr, &input[start..parser.pos]));
+                    text_start = parser.pos;
+                }
+                Err(e) => {
+                    errors.push(e);
+                    parser.pos = start + 1;
+                }
             }
         }
+        parts.push(Part::Text(&input[text_start..]));
 
-        None
+        (parts, errors)
     }
 
-    fn parse_token(input: &str) -> Option<Token> {
-        let mut iter = input.chars();
-        let start_char = iter.next().unwrap(); // guaranteed to have at least one char
-        let end_char = iter.last();
-
-        if start_char == '\'' && end_char.is_some() && end_char.unwrap() == '\'' {
-            return Some(Token::StringLit(input[1..input.len() - 1].replace("\\'", "'")));
-        }
+    fn eval_expr(&self, input: &str, expr: &Expr, context: &mut impl Context) -> Option<Token> {
+        match expr {
+            Expr::Token(token) => Some(token.clone()),
 
-        if start_char.is_numeric() {
-            return if let Ok(number) = input.parse::<f64>() {
-                Some(Token::NumberLit(number))
-            }
-            else if let Ok(number) = input.replace(",", "").parse::<f64>() {
-                // Allow commas
-                // (not doing in the initial parse; the idea being that numbers with commas are not common)
-                Some(Token::NumberLit(number))
-            }
-            else {
-                None
+            Expr::Var(name) => {
+                let res = context.on_var_eval(name);
+                if res.is_none() {
+                    context.on_error(input, ValidationError::UnknownVariable(name.clone()));
+                }
+                res
             }
-        }
 
-        if input.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
-            return Some(Token::Identifier(input.to_owned()));
+            Expr::Filter(name, args) => {
+                let args = args.iter()
+                    .map(|arg| Some(match (arg, self.eval_expr(input, arg, context)?) {
+                        (Expr::Token(_), token) => token,
+                        (_, Token::StringLit(value)) => Token::from_value(value),
+                        (_, token) => token
+                    }))
+                    .collect::<Option<Vec<_>>>()?;
+
+                let res = context.on_filter_eval(name, &args)
+                    .or_else(|| self.filters.get(name).and_then(|filter| filter(&args)));
+                if res.is_none() {
+                    context.on_error(input, ValidationError::FilterFailed(name.clone()));
+                }
+                res.map(Token::StringLit)
+            }
         }
-
-        None
     }
 
     pub fn eval(&self, input: &str) -> String {
@@ -104,119 +321,122 @@ impl Parser {
     }
 
     pub fn eval_with_context(&self, input: &str, context: &mut impl Context) -> String {
-        let mut output: Vec<u8> = Vec::with_capacity(input.len());
-
-        let mut start_expr = false;
-        let mut in_filter = false;
-        let mut checkpoint: usize = 0;
-        let mut tokens: Vec<Token> = Vec::new();
-        let mut token_start: usize = 0;
-        let mut in_string = false;
-        let mut start_escape = false;
-
-        // Iterate through the bytes directly for the sake of simplicity
-        // (it's also faster than going through char())
-        // A caveat is that the "syntax parsing" portion of the parser has
-        // no knowledge of Unicode characters; it doesn't need to anyways, UTF-8
-        // sequences do not conflict with normal ascii characters.
-        for (i, c) in input.bytes().enumerate() {
-            output.push(c);
-
-            if in_filter {
-                // Continue if string char is escaped
-                if start_escape {
-                    start_escape = false;
-                    continue;
-                }
+        let (parts, errors) = Self::parse(input);
+        for e in errors {
+            context.on_error(input, e);
+        }
 
-                // Check separator and expr close
-                match c {
-                    b')' => 'filter_close: {
-                        if in_string { break 'filter_close; }
-
-                        // Parse token (if it hasnt been terminated by a trailing separator yet)
-                        if token_start != 0 {
-                            let res = Self::parse_token(&input[token_start..i]);
-                            if let Some(token) = res {
-                                tokens.push(token);
-                                token_start = 0;
-                            }
-                            else {
-                                warn!("Invalid token in '{}' (at pos {})", input, token_start);
-                                token_start = 0;
-                                tokens.clear();
-                                in_filter = false;
-                                break 'filter_close;
-                            }
-                        }
-
-                        if let Some(res) = self.eval_filter(&tokens, context) {
-                            output.truncate(checkpoint);
-                            output.extend(res.bytes());
-                        }
-                        else {
-                            warn!("Filter evaluation failed in '{}' (at pos {})", input, i);
-                        }
-
-                        tokens.clear();
-                        in_filter = false;
-                    },
-
-                    b' ' => if !in_string && token_start != 0 {
-                        let res = Self::parse_token(&input[token_start..i]);
-                        if let Some(token) = res {
-                            tokens.push(token);
-                        }
-                        else {
-                            warn!("Invalid token in '{}' (at pos {})", input, token_start);
-                            tokens.clear();
-                            in_filter = false;
-                        }
-                        token_start = 0;
-                    },
-
-                    b'\'' => {
-                        if token_start == 0 {
-                            token_start = i;
-                            in_string = true;
-                        }
-                        else {
-                            in_string = false;
-                        }
+        let mut output = String::with_capacity(input.len());
+        for part in parts {
+            match part {
+                Part::Text(text) => output.push_str(text),
+                Part::Expr(expr, raw) => {
+                    if let Some(res) = self.eval_expr(input, &expr, context) {
+                        output.push_str(&res.to_string());
                     }
-
-                    b'\\' => if in_string {
-                        start_escape = true;
+                    else {
+                        output.push_str(raw);
                     }
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Evaluate the template with a context that returns an empty string on any filter expr
+    pub fn remove_filters(&self, input: &str) -> String {
+        self.eval_with_context(input, &mut FilterRemovalContext {})
+    }
+
+    /// Parse the template and check its expressions, returning the errors instead of logging them.
+    /// `extra_filters` and `extra_vars` are provided by the context that it will be evaluated with.
+    /// Filters are only evaluated if all of their arguments are literals.
+    pub fn validate(&self, input: &str, extra_filters: &[&str], extra_vars: &[&str]) -> Vec<ValidationError> {
+        let (parts, mut errors) = Self::parse(input);
+        for part in parts {
+            if let Part::Expr(expr, _) = part {
+                self.validate_expr(&expr, extra_filters, extra_vars, &mut errors);
+            }
+        }
+        errors
+    }
+
+    fn validate_expr(&self, expr: &Expr, extra_filters: &[&str], extra_vars: &[&str], errors: &mut Vec<ValidationError>) {
+        match expr {
+            Expr::Token(_) => (),
+
+            Expr::Var(name) => if !extra_vars.contains(&name.as_str()) {
+                errors.push(ValidationError::UnknownVariable(name.clone()));
+            }
+
+            Expr::Filter(name, args) => {
+                for arg in args {
+                    self.validate_expr(arg, extra_filters, extra_vars, errors);
+                }
 
-                    _ => if token_start == 0 {
-                        token_start = i;
+                if extra_filters.contains(&name.as_str()) {
+                    return;
+                }
+                let Some(filter) = self.filters.get(name) else {
+                    errors.push(ValidationError::UnknownFilter(name.clone()));
+                    return;
+                };
+
+                let literal_args: Option<Vec<Token>> = args.iter()
+                    .map(|arg| if let Expr::Token(token) = arg { Some(token.clone()) } else { None })
+                    .collect();
+                if let Some(literal_args) = literal_args {
+                    if filter(&literal_args).is_none() {
+                        errors.push(ValidationError::FilterFailed(name.clone()));
                     }
                 }
-                continue;
             }
+        }
+    }
+}
 
-            if start_expr {
-                // Check expression opening
-                if c == b'(' { // Filter expression
-                    in_filter = true;
-                }
-                start_expr = false;
-                continue;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct TestContext();
+
+    impl Context for TestContext {
+        fn on_filter_eval(&mut self, name: &str, args: &[Token]) -> Option<String> {
+            match name {
+                "id" => Some(args.first()?.to_string()),
+                "is_number" => Some(matches!(args.first()?, Token::NumberLit(_)).to_string()),
+                _ => None
             }
+        }
 
-            // Check for expression start
-            if c == b'$' {
-                start_expr = true;
-                checkpoint = output.len() - 1; // before the starting char
+        fn on_var_eval(&mut self, name: &str) -> Option<Token> {
+            match name {
+                "padded" => Some(Token::StringLit("007".to_owned())),
+                _ => None
             }
         }
+    }
 
-        unsafe { String::from_utf8_unchecked(output) }
+    fn eval(input: &str) -> String {
+        Parser::new(&[]).eval_with_context(input, &mut TestContext())
     }
 
-    /// Evaluate the template with a context that returns an empty string on any filter expr
-    pub fn remove_filters(&self, input: &str) -> String {
-        self.eval_with_context(input, &mut FilterRemovalContext {})
+    #[test]
+    fn top_level_results_are_raw() {
+        assert_eq!(eval("$(id '007')"), "007");
+        assert_eq!(eval("$(id '1.50')"), "1.50");
+        assert_eq!(eval("$(id '1e3')"), "1e3");
+        assert_eq!(eval("No. ${padded}"), "No. 007");
+    }
+
+    #[test]
+    fn nested_results_are_numbers() {
+        assert_eq!(eval("$(is_number $(id '007'))"), "true");
+        assert_eq!(eval("$(is_number ${padded})"), "true");
+        assert_eq!(eval("$(id $(id '1.50'))"), "1.5");
+        assert_eq!(eval("$(is_number '007')"), "false");
+        assert_eq!(eval("$(is_number $(id 'abc'))"), "false");
     }
-}
\ No newline at end of file
+}
@@ -1,11 +1,16 @@
 use super::{template, Hachimi};
 
-pub static LIST: [(&str, template::Filter); 3] = [
+pub static LIST: [(&str, template::Filter); 5] = [
     ("plural", plural),
     ("ordinal", ordinal),
-    ("month", month)
+    ("month", month),
+    ("if", if_),
+    ("select", select)
 ];
 
+// Filters that are handled by the TextGenerator hook's context
+pub static TEXT_GENERATOR_FILTERS: [&str; 5] = ["nb", "anchor", "scale", "ho", "vo"];
+
 // $(plural n 'plural_type_0' 'plural_type_1' ...)
 fn plural(args: &[template::Token]) -> Option<String> {
     if args.len() < 2 { return None; }
@@ -24,7 +29,7 @@ fn plural(args: &[template::Token]) -> Option<String> {
 
 // $(ordinal n)
 fn ordinal(args: &[template::Token]) -> Option<String> {
-    if let template::Token::NumberLit(n) = args[0] {
+    if let template::Token::NumberLit(n) = *args.first()? {
         let localized_data = Hachimi::instance().localized_data.load();
         let i = localized_data.ordinal_form.resolve(n as u64);
         let ordinal_type = localized_data.config.ordinal_types.get(i)?;
@@ -36,10 +41,45 @@ fn ordinal(args: &[template::Token]) -> Option<String> {
 
 // $(month n)
 fn month(args: &[template::Token]) -> Option<String> {
-    if let template::Token::NumberLit(i) = args[0] {
+    if let template::Token::NumberLit(i) = *args.first()? {
         let localized_data = Hachimi::instance().localized_data.load();
         return localized_data.config.months.get((i as usize).saturating_sub(1)).cloned();
     }
 
     None
-}
\ No newline at end of file
+}
+
+// $(if cond 'text_if_true' 'text_if_false')
+// cond is true if it's a non-zero number or a non-empty string
+fn if_(args: &[template::Token]) -> Option<String> {
+    let cond = match args.first()? {
+        template::Token::NumberLit(n) => *n != 0.0,
+        template::Token::StringLit(s) => !s.is_empty(),
+        template::Token::Identifier(_) => return None
+    };
+
+    if cond {
+        Some(args.get(1)?.to_string())
+    }
+    else {
+        Some(args.get(2).map(|t| t.to_string()).unwrap_or_default())
+    }
+}
+
+// $(select value 'key_1' 'text_1' 'key_2' 'text_2' ... 'default_text')
+// Keys are compared as strings, so 1 and '1' are the same key
+fn select(args: &[template::Token]) -> Option<String> {
+    let (value, cases) = args.split_first()?;
+    let value = value.to_string();
+    for case in cases.chunks(2) {
+        match case {
+            [key, text] => if key.to_string() == value {
+                return Some(text.to_string());
+            }
+            [default] => return Some(default.to_string()),
+            _ => unreachable!()
+        }
+    }
+
+    None
+}
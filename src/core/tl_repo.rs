@@ -1,30 +1,192 @@
 use std::{fs, io::{Read, Write}, path::{Path, PathBuf}, sync::{atomic::{self, AtomicUsize}, Arc, Mutex}};
 
 use arc_swap::ArcSwap;
-use fnv::FnvHashMap;
+use base64::prelude::*;
+use ed25519_dalek::{Signature, VerifyingKey};
+use fnv::{FnvHashMap, FnvHashSet};
 use rust_i18n::t;
 use serde::{Deserialize, Serialize};
 use size::Size;
 use threadpool::ThreadPool;
 
-use super::{gui::SimpleYesNoDialog, hachimi::LocalizedData, http::{self, AsyncRequest}, utils, Error, Gui, Hachimi};
+use super::{gui::SimpleYesNoDialog, hachimi::Config, http::{self, AsyncRequest}, utils, Error, Gui, Hachimi};
 
 #[derive(Deserialize)]
 pub struct RepoInfo {
     pub name: String,
     pub index: String,
-    pub short_desc: Option<String>
+    // Tried in order if the index can't be reached
+    #[serde(default)]
+    pub index_mirrors: Vec<String>,
+    pub short_desc: Option<String>,
+    // ed25519 public key (base64), pinned in the config when the repo is selected (or on the next update
+    // check if it was selected before the repo was signed)
+    pub public_key: Option<String>,
+    // Add-ons (font packs, texture packs...) are layered on top of the main repo
+    #[serde(default)]
+    pub addon: bool,
+    #[serde(default)]
+    pub priority: i32
+}
+
+// Add-on repo in the config (translation_repo_addons)
+#[derive(Deserialize, Serialize, Clone)]
+pub struct RepoSubscription {
+    pub index: String,
+    #[serde(default)]
+    pub index_mirrors: Vec<String>,
+    pub public_key: Option<String>,
+    // Higher priority add-ons are layered on top of lower priority ones
+    #[serde(default)]
+    pub priority: i32
+}
+
+impl RepoSubscription {
+    pub fn from_info(info: &RepoInfo) -> RepoSubscription {
+        RepoSubscription {
+            index: info.index.clone(),
+            index_mirrors: info.index_mirrors.clone(),
+            public_key: info.public_key.clone(),
+            priority: info.priority
+        }
+    }
+
+    /// The add-on's localized data dir, relative to the data dir.
+    pub fn dir(&self) -> String {
+        let hash = blake3::hash(self.index.as_bytes()).to_hex();
+        format!("{}/{}", ADDON_REPOS_DIR, &hash[..16])
+    }
+}
+
+/// The add-on dirs to layer on top of the localized data dir, lowest priority first.
+pub fn addon_dirs(config: &Config) -> Vec<String> {
+    let mut addons: Vec<&RepoSubscription> = config.translation_repo_addons.iter().collect();
+    // Stable sort, so add-ons with the same priority keep their config order
+    addons.sort_by_key(|addon| addon.priority);
+    addons.into_iter().map(|addon| addon.dir()).collect()
+}
+
+fn meta_index_urls(config: &Config) -> Vec<String> {
+    std::iter::once(&config.meta_index_url).chain(config.meta_index_mirrors.iter()).cloned().collect()
 }
 
 pub fn new_meta_index_request() -> AsyncRequest<Vec<RepoInfo>> {
-    let meta_index_url = &Hachimi::instance().config.load().meta_index_url;
-    AsyncRequest::with_json_response(ureq::get(meta_index_url))
+    AsyncRequest::with_json_response(meta_index_urls(&Hachimi::instance().config.load()))
+}
+
+/// Fills in the public keys of the configured repos that don't have one from the meta index, so that
+/// repos selected before they were signed get pinned too. Returns None if nothing was pinned.
+/// Only the primary meta index is trusted for this since a pinned key is never replaced; if a wrong key
+/// does get pinned, clearing it (translation_repo_public_key, or the add-on's public_key) in the config
+/// lets it be pinned again on the next update check.
+fn pin_public_keys(config: &Config) -> Result<Option<Config>, Error> {
+    let main_unpinned = config.translation_repo_index.is_some() && config.translation_repo_public_key.is_none();
+    if !main_unpinned && config.translation_repo_addons.iter().all(|addon| addon.public_key.is_some()) {
+        return Ok(None);
+    }
+
+    let repo_list: Vec<RepoInfo> = http::get_json(&config.meta_index_url)?;
+    let find_key = |index: &str| repo_list.iter()
+        .find(|repo| repo.index == index)
+        .and_then(|repo| repo.public_key.clone());
+
+    let mut new_config = config.clone();
+    let mut pinned = false;
+    if main_unpinned {
+        new_config.translation_repo_public_key = find_key(config.translation_repo_index.as_deref().unwrap_or_default());
+        pinned |= new_config.translation_repo_public_key.is_some();
+    }
+    for addon in new_config.translation_repo_addons.iter_mut().filter(|addon| addon.public_key.is_none()) {
+        addon.public_key = find_key(&addon.index);
+        pinned |= addon.public_key.is_some();
+    }
+
+    Ok(pinned.then_some(new_config))
+}
+
+// Detached signature of the index file (base64), at the index's url + SIGNATURE_EXT
+const SIGNATURE_EXT: &str = ".sig";
+
+/// Checks the ed25519 signature of `data`. The key and signature are base64 encoded.
+fn verify_signature(data: &[u8], signature: &str, public_key: &str) -> Result<(), Error> {
+    let decode = |s: &str, what: &str| BASE64_STANDARD.decode(s.trim())
+        .map_err(|e| Error::InvalidSignature(format!("Failed to decode {}: {}", what, e)));
+
+    let key_bytes: [u8; 32] = decode(public_key, "public key")?.try_into()
+        .map_err(|_| Error::InvalidSignature("Invalid public key length".to_owned()))?;
+    let key = VerifyingKey::from_bytes(&key_bytes)
+        .map_err(|e| Error::InvalidSignature(format!("Invalid public key: {}", e)))?;
+    let signature = Signature::from_slice(&decode(signature, "signature")?)
+        .map_err(|e| Error::InvalidSignature(e.to_string()))?;
+
+    key.verify_strict(data, &signature)
+        .map_err(|_| Error::InvalidSignature("Signature doesn't match the repo's public key".to_owned()))
+}
+
+/// Fetches the repo's index from the first of `index_urls` that can be reached. The signature is fetched
+/// from the same url as the index, so that they always match.
+fn get_repo_index(agent: &ureq::Agent, index_urls: &[String], public_key: Option<&str>) -> Result<RepoIndex, Error> {
+    let get_bytes = |url: &str| http::read_bytes(agent.get(url).call()?);
+
+    http::with_failover(index_urls, |index_url| {
+        let data = get_bytes(index_url)?;
+        let Some(public_key) = public_key else {
+            return Ok(serde_json::from_slice(&data)?);
+        };
+
+        // Verified against the exact bytes that were signed
+        let signature_url = index_url.to_owned() + SIGNATURE_EXT;
+        let signature = match get_bytes(&signature_url) {
+            Ok(v) => String::from_utf8_lossy(&v).into_owned(),
+            Err(Error::HttpError(e)) if matches!(*e, ureq::Error::Status(404, _)) => {
+                return Err(Error::InvalidSignature(format!("Index is not signed ({} not found)", signature_url)));
+            }
+            Err(e) => return Err(e)
+        };
+        verify_signature(&data, &signature, public_key)?;
+
+        Ok(serde_json::from_slice(&data)?)
+    })
+}
+
+// Index of a sideloaded repo, at the root of the zip or folder (the files are in the index's zip_dir like in the repo's zip)
+const SIDELOAD_INDEX_FILENAME: &str = "index.json";
+
+/// Reads the index of a sideloaded repo zip or folder, the signature is checked the same way as the remote index's.
+fn read_sideload_index(path: &Path, public_key: Option<&str>) -> Result<RepoIndex, Error> {
+    let signature_filename = SIDELOAD_INDEX_FILENAME.to_owned() + SIGNATURE_EXT;
+    let (data, signature) = if path.is_dir() {
+        (fs::read(path.join(SIDELOAD_INDEX_FILENAME))?, fs::read(path.join(&signature_filename)).ok())
+    }
+    else {
+        let mut zip_archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+        let mut read_file = |name: &str| -> Result<Vec<u8>, Error> {
+            let mut data = Vec::new();
+            zip_archive.by_name(name)?.read_to_end(&mut data)?;
+            Ok(data)
+        };
+        (read_file(SIDELOAD_INDEX_FILENAME)?, read_file(&signature_filename).ok())
+    };
+
+    if let Some(public_key) = public_key {
+        let Some(signature) = signature else {
+            return Err(Error::InvalidSignature(format!("Index is not signed ({} not found)", signature_filename)));
+        };
+        verify_signature(&data, &String::from_utf8_lossy(&signature), public_key)?;
+    }
+
+    Ok(serde_json::from_slice(&data)?)
 }
 
 #[derive(Deserialize)]
 struct RepoIndex {
     base_url: String,
     zip_url: String,
+    // Tried in order if base_url/zip_url can't be reached
+    #[serde(default)]
+    base_url_mirrors: Vec<String>,
+    #[serde(default)]
+    zip_url_mirrors: Vec<String>,
     zip_dir: String,
     files: Vec<RepoFile>
 }
@@ -47,17 +209,114 @@ impl RepoFile {
     }
 }
 
+// A repo and where it gets downloaded to
+#[derive(Clone)]
+struct RepoTarget {
+    index_url: String,
+    index_mirrors: Vec<String>,
+    public_key: Option<String>,
+    // Relative to the data dir
+    dir: String,
+    cache_filename: String
+}
+
+impl RepoTarget {
+    fn main(config: &Config) -> RepoTarget {
+        RepoTarget {
+            // Empty if the repo was sideloaded without setting one
+            index_url: config.translation_repo_index.clone().unwrap_or_default(),
+            index_mirrors: config.translation_repo_index_mirrors.clone(),
+            public_key: config.translation_repo_public_key.clone(),
+            dir: LOCALIZED_DATA_DIR.to_owned(),
+            cache_filename: REPO_CACHE_FILENAME.to_owned()
+        }
+    }
+
+    fn all(config: &Config) -> Vec<RepoTarget> {
+        let mut targets = Vec::new();
+        if config.translation_repo_index.is_some() {
+            targets.push(Self::main(config));
+        }
+        for addon in config.translation_repo_addons.iter() {
+            let dir = addon.dir();
+            targets.push(RepoTarget {
+                index_url: addon.index.clone(),
+                index_mirrors: addon.index_mirrors.clone(),
+                public_key: addon.public_key.clone(),
+                cache_filename: dir.clone() + REPO_CACHE_FILENAME,
+                dir
+            });
+        }
+        targets
+    }
+
+    // Also includes the main repo if it was sideloaded without an index url
+    fn all_local(config: &Config) -> Vec<RepoTarget> {
+        let mut targets = Self::all(config);
+        if config.translation_repo_index.is_none() {
+            targets.insert(0, Self::main(config));
+        }
+        targets
+    }
+
+    // Primary url first
+    fn index_urls(&self) -> Vec<String> {
+        std::iter::once(&self.index_url).chain(self.index_mirrors.iter()).cloned().collect()
+    }
+
+    fn is_main(&self) -> bool {
+        self.dir == LOCALIZED_DATA_DIR
+    }
+
+    fn dir_path(&self, ext: &str) -> PathBuf {
+        Hachimi::instance().get_data_path(self.dir.clone() + ext)
+    }
+
+    fn cache_path(&self, ext: &str) -> PathBuf {
+        Hachimi::instance().get_data_path(self.cache_filename.clone() + ext)
+    }
+}
+
+// Where the files of an update come from
+#[derive(Clone)]
+enum UpdateSource {
+    Remote,
+    // Sideloaded repos, see Updater::sideload
+    LocalZip(PathBuf),
+    LocalDir(PathBuf)
+}
+
 #[derive(Clone)]
 struct UpdateInfo {
+    target: RepoTarget,
+    source: UpdateSource,
     base_url: String,
     zip_url: String,
+    base_url_mirrors: Vec<String>,
+    zip_url_mirrors: Vec<String>,
     zip_dir: String,
     files: Vec<RepoFile>, // only contains files needed for update
+    index_files: Vec<RepoFile>, // every file in the index, for verification
     is_new_repo: bool,
+    // Retry of an update that failed verification, continues from the staging dir as it was left
+    resume_staging: bool,
     cached_files: FnvHashMap<String, String>, // from repo cache
     size: usize
 }
 
+impl UpdateInfo {
+    // Primary url first
+    fn file_urls(&self, path: &str) -> Vec<String> {
+        std::iter::once(&self.base_url).chain(self.base_url_mirrors.iter())
+            .map(|base_url| utils::concat_unix_path(base_url, path))
+            .collect()
+    }
+
+    fn zip_urls(&self) -> Vec<String> {
+        std::iter::once(&self.zip_url).chain(self.zip_url_mirrors.iter()).cloned().collect()
+    }
+}
+
 #[derive(Default, Clone)]
 pub struct UpdateProgress {
     pub current: usize,
@@ -83,11 +342,20 @@ struct RepoCache {
 #[derive(Default)]
 pub struct Updater {
     update_check_mutex: Mutex<()>,
-    new_update: ArcSwap<Option<UpdateInfo>>,
+    // Held while the localized data dir is being modified (update, rollback)
+    update_mutex: Mutex<()>,
+    new_update: ArcSwap<Vec<UpdateInfo>>,
+    // Files that couldn't be downloaded in the last update
+    failed_update: ArcSwap<Vec<UpdateInfo>>,
     progress: ArcSwap<Option<UpdateProgress>>
 }
 
 const LOCALIZED_DATA_DIR: &str = "localized_data";
+const ADDON_REPOS_DIR: &str = "tl_repos";
+// Updates are downloaded to the repo's dir + STAGING_EXT and then swapped with the repo's dir
+const STAGING_EXT: &str = ".staging";
+// The previous version of the repo's dir and cache, for rollbacks
+const BACKUP_EXT: &str = ".backup";
 const CHUNK_SIZE: usize = 8192; // 8KiB
 const NUM_THREADS: usize = 8;
 const INCREMENTAL_UPDATE_LIMIT: usize = 200;
@@ -101,35 +369,109 @@ struct DownloadJob {
 impl DownloadJob {
     fn new() -> DownloadJob {
         DownloadJob {
-            agent: ureq::Agent::new(),
+            agent: http::agent(),
             hasher: blake3::Hasher::new(),
             buffer: [0u8; CHUNK_SIZE]
         }
     }
 
-    fn execute(&mut self, file_path: &Path, url: &str, file_hash: &str, add_bytes: impl Fn(usize)) -> Result<String, Error> {
+    fn execute(&mut self, file_path: &Path, urls: &[String], file_hash: &str, add_bytes: impl Fn(usize)) -> Result<String, Error> {
         if let Some(parent) = Path::new(file_path).parent() {
             fs::create_dir_all(parent)?;
         }
-        let mut file = fs::File::create(file_path)?;
 
-        let res = self.agent.get(url).call()?;
-        http::download_file_buffered(res, &mut file, &mut self.buffer, |bytes| {
-            self.hasher.update(bytes);
-            add_bytes(bytes.len());
-        })?;
-        file.sync_data()?;
+        // Downloaded to a separate file so that an interrupted download can be continued later
+        // without touching the old version of the file
+        let part_path = get_part_path(file_path);
+        let mut resumed = fs::metadata(&part_path).map(|m| m.len() > 0).unwrap_or(false);
+        loop {
+            let mut counted = 0;
+            http::download_file_resumable(&self.agent, urls, &part_path, &mut self.buffer, |downloaded, _| {
+                if downloaded > counted {
+                    add_bytes(downloaded - counted);
+                    counted = downloaded;
+                }
+            })?;
+
+            // Hash the file
+            self.hasher.update_reader(fs::File::open(&part_path)?)?;
+            let hash = self.hasher.finalize().to_hex().to_string();
+            self.hasher.reset();
+
+            if hash == file_hash {
+                fs::rename(&part_path, file_path)?;
+                return Ok(hash);
+            }
+
+            fs::remove_file(&part_path)?;
+            // The partial file might've been from an older version, try again from scratch
+            if !resumed {
+                return Err(Error::FileHashMismatch(file_path.to_str().unwrap_or("").to_string()));
+            }
+            resumed = false;
+        }
+    }
+}
+
+// Only replaces the files in dst that also exist in src
+fn link_or_copy_dir(src: &Path, dst: &Path) -> Result<(), Error> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let src_path = entry.path();
+        let dst_path = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            link_or_copy_dir(&src_path, &dst_path)?;
+            continue;
+        }
 
-        // Hash the file
-        let hash = self.hasher.finalize().to_hex().to_string();
-        if hash != file_hash {
-            return Err(Error::FileHashMismatch(file_path.to_str().unwrap_or("").to_string()));
+        if dst_path.exists() {
+            fs::remove_file(&dst_path)?;
+        }
+        // Hard links are fine since updated files are always replaced, never written to
+        if fs::hard_link(&src_path, &dst_path).is_err() {
+            fs::copy(&src_path, &dst_path)?;
         }
+    }
+    Ok(())
+}
 
-        self.hasher.reset();
+fn remove_files_except_parts(dir: &Path) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            remove_files_except_parts(&path)?;
+        }
+        else if path.extension().map(|ext| ext != "part").unwrap_or(true) {
+            fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
+
+// Either path can be missing
+fn swap_paths(a: &Path, b: &Path) -> Result<(), Error> {
+    let mut tmp_path = a.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let tmp_path = PathBuf::from(tmp_path);
 
-        Ok(hash)
+    let a_exists = a.exists();
+    if a_exists {
+        fs::rename(a, &tmp_path)?;
+    }
+    if b.exists() {
+        fs::rename(b, a)?;
+    }
+    if a_exists {
+        fs::rename(&tmp_path, b)?;
     }
+    Ok(())
+}
+
+fn get_part_path(path: &Path) -> PathBuf {
+    let mut part_path = path.as_os_str().to_owned();
+    part_path.push(".part");
+    PathBuf::from(part_path)
 }
 
 impl Updater {
@@ -148,19 +490,92 @@ impl Updater {
         };
 
         let hachimi = Hachimi::instance();
-        let config = hachimi.config.load();
-        let Some(index_url) = &config.translation_repo_index else {
+        let mut config = hachimi.config.load_full();
+        if RepoTarget::all(&config).is_empty() {
             return Ok(());
-        };
-        let ld_dir_path = config.localized_data_dir.as_ref().map(|p| hachimi.get_data_path(p));
+        }
+
+        // The meta index being unreachable shouldn't block the update check, the keys get pinned next time
+        match pin_public_keys(&config) {
+            Ok(Some(new_config)) => {
+                info!("Pinned the public keys of the translation repos");
+                hachimi.save_and_reload_config(new_config)?;
+                config = hachimi.config.load_full();
+            }
+            Ok(None) => (),
+            Err(e) => warn!("Failed to pin the translation repos' public keys: {}", e)
+        }
+        let targets = RepoTarget::all(&config);
 
         if let Some(mutex) = Gui::instance() {
             mutex.lock().unwrap().show_notification(&t!("notification.checking_for_tl_updates"));
         }
 
-        let index: RepoIndex = http::get_json(index_url)?;
+        // A broken repo shouldn't hold back the updates of the other ones
+        let mut updates = Vec::new();
+        let mut update_size = 0;
+        let mut has_errors = false;
+        for target in targets {
+            match Self::check_repo(&config, target, pedantic) {
+                Ok(Some((update_info, size))) => {
+                    updates.push(update_info);
+                    update_size += size;
+                }
+                Ok(None) => (),
+                Err(e) => {
+                    error!("{}", e);
+                    has_errors = true;
+                    // Don't let a tampered repo go unnoticed
+                    if let Error::InvalidSignature(_) = e {
+                        if let Some(mutex) = Gui::instance() {
+                            mutex.lock().unwrap().show_notification(&t!("notification.update_failed", reason = e.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+
+        if !updates.is_empty() {
+            self.new_update.store(Arc::new(updates));
+            if let Some(mutex) = Gui::instance() {
+                mutex.lock().unwrap().show_window(Box::new(SimpleYesNoDialog::new(
+                    &t!("tl_update_dialog.title"),
+                    &t!("tl_update_dialog.content", size = Size::from_bytes(update_size)),
+                    |ok| {
+                        if !ok { return; }
+                        Hachimi::instance().tl_updater.clone().run();
+                    }
+                )));
+            }
+        }
+        else if !has_errors {
+            if let Some(mutex) = Gui::instance() {
+                mutex.lock().unwrap().show_notification(&t!("notification.no_tl_updates"));
+            }
+        }
+        
+        Ok(())
+    }
+
+    /// Returns the repo's update and its download size, if there's one.
+    fn check_repo(config: &Config, target: RepoTarget, pedantic: bool) -> Result<Option<(UpdateInfo, usize)>, Error> {
+        let index = get_repo_index(&http::agent(), &target.index_urls(), target.public_key.as_deref())?;
+        Self::new_update_info(config, target, index, UpdateSource::Remote, pedantic)
+    }
+
+    /// Compares the index with the repo cache, returns the update and its download size if there's one.
+    fn new_update_info(
+        config: &Config, target: RepoTarget, index: RepoIndex, source: UpdateSource, pedantic: bool
+    ) -> Result<Option<(UpdateInfo, usize)>, Error> {
+        let hachimi = Hachimi::instance();
+        let ld_dir_path = if target.is_main() {
+            config.localized_data_dir.as_ref().map(|p| hachimi.get_data_path(p))
+        }
+        else {
+            Some(target.dir_path(""))
+        };
 
-        let cache_path = hachimi.get_data_path(REPO_CACHE_FILENAME);
+        let cache_path = target.cache_path("");
         let repo_cache = if fs::metadata(&cache_path).is_ok() {
             let json = fs::read_to_string(&cache_path)?;
             serde_json::from_str(&json)?
@@ -171,6 +586,7 @@ impl Updater {
 
         let is_new_repo = index.base_url != repo_cache.base_url;
         let mut update_files: Vec<RepoFile> = Vec::new();
+        let mut index_files: Vec<RepoFile> = Vec::new();
         let mut update_size: usize = 0;
         let mut total_size: usize = 0;
         for file in index.files.iter() {
@@ -201,36 +617,32 @@ impl Updater {
                 update_files.push(file.clone());
                 update_size += file.size;
             }
+            index_files.push(file.clone());
             total_size += file.size;
         }
 
-        let is_zip_download = update_files.len() > INCREMENTAL_UPDATE_LIMIT;
-        if !update_files.is_empty() {
-            self.new_update.store(Arc::new(Some(UpdateInfo {
-                is_new_repo,
-                base_url: index.base_url,
-                zip_url: index.zip_url,
-                zip_dir: index.zip_dir,
-                files: update_files,
-                cached_files: repo_cache.files,
-                size: if is_zip_download { total_size } else { update_size }
-            })));
-            if let Some(mutex) = Gui::instance() {
-                mutex.lock().unwrap().show_window(Box::new(SimpleYesNoDialog::new(
-                    &t!("tl_update_dialog.title"),
-                    &t!("tl_update_dialog.content", size = Size::from_bytes(update_size)),
-                    |ok| {
-                        if !ok { return; }
-                        Hachimi::instance().tl_updater.clone().run();
-                    }
-                )));
-            }
+        if update_files.is_empty() {
+            return Ok(None);
         }
-        else if let Some(mutex) = Gui::instance() {
-            mutex.lock().unwrap().show_notification(&t!("notification.no_tl_updates"));
-        }
-        
-        Ok(())
+
+        // Local sources only extract/copy the files that need updating
+        let is_zip_download = matches!(source, UpdateSource::Remote) && update_files.len() > INCREMENTAL_UPDATE_LIMIT;
+        Ok(Some((UpdateInfo {
+            target,
+            source,
+            is_new_repo,
+            resume_staging: false,
+            base_url: index.base_url,
+            zip_url: index.zip_url,
+            base_url_mirrors: index.base_url_mirrors,
+            zip_url_mirrors: index.zip_url_mirrors,
+            zip_dir: index.zip_dir,
+            files: update_files,
+            index_files,
+            // The old repo's files are gone after the swap
+            cached_files: if is_new_repo { FnvHashMap::default() } else { repo_cache.files },
+            size: if is_zip_download { total_size } else { update_size }
+        }, update_size)))
     }
 
     pub fn run(self: Arc<Self>) {
@@ -246,78 +658,363 @@ impl Updater {
     }
 
     fn run_internal(self: Arc<Self>) -> Result<(), Error> {
-        let Some(update_info) = (**self.new_update.load()).clone() else {
+        let Ok(_guard) = self.update_mutex.try_lock() else {
             return Ok(());
         };
-        self.new_update.store(Arc::new(None));
+        let updates = self.new_update.swap(Arc::default());
+        if updates.is_empty() {
+            return Ok(());
+        }
+        self.failed_update.store(Arc::default());
+        self.clone().install_updates(&updates)
+    }
 
-        self.progress.store(Arc::new(Some(UpdateProgress::new(0, update_info.size))));
+    // Must be called with the update mutex held
+    fn install_updates(self: Arc<Self>, updates: &[UpdateInfo]) -> Result<(), Error> {
         if let Some(mutex) = Gui::instance() {
             mutex.lock().unwrap().update_progress_visible = true;
         }
 
-        // Empty the localized data so files couldnt be accessed while update is in progress
+        // Only the repos in this update should be rolled back together
         let hachimi = Hachimi::instance();
-        hachimi.localized_data.store(Arc::new(LocalizedData::default()));
-
-        // Clear the localized data if downloading from a new repo
-        let localized_data_dir = hachimi.get_data_path(LOCALIZED_DATA_DIR);
-        if update_info.is_new_repo {
-            // rm -rf
-            if let Ok(meta) = fs::metadata(&localized_data_dir) {
-                if meta.is_dir() {
-                    fs::remove_dir_all(&localized_data_dir)?;
+        for target in RepoTarget::all_local(&hachimi.config.load()) {
+            if updates.iter().any(|u| u.target.dir == target.dir) {
+                continue;
+            }
+            let backup_dir = target.dir_path(BACKUP_EXT);
+            if backup_dir.is_dir() {
+                fs::remove_dir_all(&backup_dir)?;
+            }
+            let cache_backup_path = target.cache_path(BACKUP_EXT);
+            if cache_backup_path.is_file() {
+                fs::remove_file(&cache_backup_path)?;
+            }
+        }
+
+        // A repo that fails shouldn't stop the others from being updated, or leave the ones
+        // that were already swapped in without their config changes and reload
+        let mut error_count = 0;
+        let mut failed_updates = Vec::new();
+        let mut update_error = None;
+        let mut main_swapped = false;
+        for update_info in updates.iter() {
+            match self.clone().update_repo(update_info) {
+                Ok((errors, failed_update)) => {
+                    error_count += errors;
+                    main_swapped |= failed_update.is_none() && update_info.target.is_main();
+                    failed_updates.extend(failed_update);
+                }
+                Err(e) => {
+                    error!("Failed to update '{}': {}", update_info.target.dir, e);
+                    // Retried as a whole, the partial downloads in the staging dir are kept
+                    failed_updates.push(update_info.clone());
+                    update_error.get_or_insert(e);
                 }
             }
         }
 
-        fs::create_dir_all(&localized_data_dir)?;
+        // Drop the download state
+        self.progress.store(Arc::new(None));
 
-        // Download the files
-        let cached_files = Arc::new(Mutex::new(update_info.cached_files.clone()));
-        // There are errors that can be ignored, let the downloader count how many non-fatal errors there are
-        let error_count = if update_info.files.len() > INCREMENTAL_UPDATE_LIMIT {
-            // It would be too slow to do a large amount of HTTP requests, so just download a zip file and extract it
-            self.clone().download_zip(&update_info, &localized_data_dir, cached_files.clone())
-        }
-        else {
-            self.clone().download_incremental(&update_info, &localized_data_dir, cached_files.clone())
-        }?; // <-- looga this question mark
-        
         // Modify the config if needed
-        if hachimi.config.load().localized_data_dir.is_none() {
+        if main_swapped && hachimi.config.load().localized_data_dir.is_none() {
             let mut config = (**hachimi.config.load()).clone();
             config.localized_data_dir = Some(LOCALIZED_DATA_DIR.to_owned());
-            hachimi.save_and_reload_config(config)?;
+            if let Err(e) = hachimi.save_and_reload_config(config) {
+                error!("Failed to save config: {}", e);
+                update_error.get_or_insert(e);
+            }
         }
 
-        // Drop the download state
-        self.progress.store(Arc::new(None));
-
         // Reload the localized data
         hachimi.load_localized_data();
 
-        // Save the repo cache (done last so if any of the previous fails, the entire update would be voided)
+        // Keep the failed files around so they can be retried without a full update check
+        let failed_count: usize = failed_updates.iter().map(|u| u.files.len()).sum();
+        self.failed_update.store(Arc::new(failed_updates));
+
+        if let Some(mutex) = Gui::instance() {
+            let mut gui = mutex.lock().unwrap();
+            if let Some(e) = &update_error {
+                gui.show_notification(&t!("notification.update_failed", reason = e.to_string()));
+            }
+            else if failed_count == 0 {
+                gui.show_notification(&t!("notification.update_completed"));
+            }
+            if error_count > 0 {
+                gui.show_notification(&t!("notification.errors_during_update", count = error_count));
+            }
+            if failed_count > 0 {
+                gui.show_window(Box::new(SimpleYesNoDialog::new(
+                    &t!("tl_retry_dialog.title"),
+                    &t!("tl_retry_dialog.content", count = failed_count),
+                    |ok| {
+                        if !ok { return; }
+                        Hachimi::instance().tl_updater.clone().retry_failed();
+                    }
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Downloads the update into the repo's staging dir and swaps it in once every file has been verified.
+    /// Returns the amount of non-fatal errors, and the failed files (as an update) if there are any.
+    fn update_repo(self: Arc<Self>, update_info: &UpdateInfo) -> Result<(usize, Option<UpdateInfo>), Error> {
+        self.progress.store(Arc::new(Some(UpdateProgress::new(0, update_info.size))));
+
+        // The current localized data is left alone (and usable) until the update is ready to be swapped in
+        let target = &update_info.target;
+        let repo_dir = target.dir_path("");
+        let staging_dir = target.dir_path(STAGING_EXT);
+        if !update_info.resume_staging || !staging_dir.is_dir() {
+            Self::prepare_staging_dir(&staging_dir, &repo_dir, update_info.is_new_repo)?;
+        }
+
+        // Download the files
+        let cached_files = Arc::new(Mutex::new(update_info.cached_files.clone()));
+        let failed_files = Arc::new(Mutex::new(Vec::new()));
+        // There are errors that can be ignored, let the downloader count how many non-fatal errors there are
+        let error_count = match &update_info.source {
+            UpdateSource::Remote => if update_info.files.len() > INCREMENTAL_UPDATE_LIMIT {
+                // It would be too slow to do a large amount of HTTP requests, so just download a zip file and extract it
+                self.clone().download_zip(update_info, &staging_dir, cached_files.clone(), failed_files.clone())
+            }
+            else {
+                self.clone().download_incremental(update_info, &staging_dir, cached_files.clone(), failed_files.clone())
+            }?, // <-- looga this question mark
+            UpdateSource::LocalZip(zip_path) => {
+                let mut buffer = [0u8; CHUNK_SIZE];
+                self.extract_zip(update_info, zip_path, &staging_dir, &cached_files, &failed_files, &mut buffer)?;
+                0
+            }
+            UpdateSource::LocalDir(src_dir) => {
+                self.copy_files(update_info, src_dir, &staging_dir, &cached_files, &failed_files)?
+            }
+        };
+
+        self.verify_files(update_info, &staging_dir, &cached_files, &failed_files)?;
+
+        // Only swap in a complete update. Otherwise the staging dir (and the partial downloads in it)
+        // is left as is, and retrying only needs to fetch the failed files into it
+        let failed_files = std::mem::take(&mut *failed_files.lock().unwrap());
+        if !failed_files.is_empty() {
+            return Ok((error_count, Some(UpdateInfo {
+                // Files missing from a sideloaded repo can still be downloaded if the repo is reachable
+                source: UpdateSource::Remote,
+                resume_staging: true,
+                size: failed_files.iter().map(|f| f.size).sum(),
+                files: failed_files,
+                cached_files: cached_files.lock().unwrap().clone(),
+                ..update_info.clone()
+            })));
+        }
+
+        // Swap in the new version, the old one is kept as the backup
+        let backup_dir = target.dir_path(BACKUP_EXT);
+        if backup_dir.is_dir() {
+            fs::remove_dir_all(&backup_dir)?;
+        }
+        let has_backup = repo_dir.is_dir();
+        if has_backup {
+            fs::rename(&repo_dir, &backup_dir)?;
+        }
+        if let Err(e) = fs::rename(&staging_dir, &repo_dir) {
+            if has_backup {
+                fs::rename(&backup_dir, &repo_dir)?;
+            }
+            return Err(e.into());
+        }
+
+        // Save the repo cache right after the swap so that it always matches the repo's dir
+        let cache_path = target.cache_path("");
+        let cache_backup_path = target.cache_path(BACKUP_EXT);
+        if has_backup && cache_path.is_file() {
+            fs::rename(&cache_path, &cache_backup_path)?;
+        }
+        else if cache_backup_path.is_file() {
+            fs::remove_file(&cache_backup_path)?;
+        }
         let repo_cache = RepoCache {
             base_url: update_info.base_url.clone(),
             files: cached_files.lock().unwrap().clone()
         };
-        let cache_path = hachimi.get_data_path(REPO_CACHE_FILENAME);
         utils::write_json_file(&repo_cache, &cache_path)?;
 
-        if let Some(mutex) = Gui::instance() {
-            let mut gui = mutex.lock().unwrap();
-            gui.show_notification(&t!("notification.update_completed"));
-            if error_count > 0 {
-                gui.show_notification(&t!("notification.errors_during_update", count = error_count));
+        Ok((error_count, None))
+    }
+
+    /// Fills the staging dir with the repo's current files, unless it's getting replaced by a new repo.
+    /// Partially downloaded files from previous attempts are kept so they can be resumed.
+    fn prepare_staging_dir(staging_dir: &Path, repo_dir: &Path, is_new_repo: bool) -> Result<(), Error> {
+        if staging_dir.is_dir() {
+            remove_files_except_parts(staging_dir)?;
+        }
+        fs::create_dir_all(staging_dir)?;
+
+        if !is_new_repo && repo_dir.is_dir() {
+            link_or_copy_dir(repo_dir, staging_dir)?;
+        }
+        Ok(())
+    }
+
+    /// Checks the files written in this update against their hashes, and that the rest of the index's files
+    /// (carried over from the current version, which was verified when it was installed) have the right size.
+    /// Files that don't match are added to the failed files and removed from the repo cache.
+    fn verify_files(
+        &self,
+        update_info: &UpdateInfo, dir: &Path, cached_files: &Mutex<FnvHashMap<String, String>>,
+        failed_files: &Mutex<Vec<RepoFile>>
+    ) -> Result<(), Error> {
+        let written_files: FnvHashSet<&str> = update_info.files.iter().map(|f| f.path.as_str()).collect();
+        let total_size = update_info.files.iter().map(|f| f.size).sum();
+        let mut current_size = 0;
+        let mut hasher = blake3::Hasher::new();
+        for repo_file in update_info.index_files.iter() {
+            let path = repo_file.get_fs_path(dir);
+            let valid = if written_files.contains(repo_file.path.as_str()) {
+                let hash = match fs::File::open(&path) {
+                    Ok(file) => {
+                        hasher.update_reader(file)?;
+                        let hash = hasher.finalize().to_hex().to_string();
+                        hasher.reset();
+                        Some(hash)
+                    }
+                    Err(_) => None
+                };
+
+                current_size += repo_file.size;
+                self.progress.store(Arc::new(Some(UpdateProgress::new(current_size, total_size))));
+                hash.as_ref() == Some(&repo_file.hash)
+            }
+            else {
+                fs::metadata(&path).is_ok_and(|m| m.is_file() && m.len() == repo_file.size as u64)
+            };
+
+            if valid {
+                continue;
+            }
+
+            warn!("File failed verification: {}", repo_file.path);
+            cached_files.lock().unwrap().remove(&repo_file.path);
+            let mut failed_files = failed_files.lock().unwrap();
+            if !failed_files.iter().any(|f| f.path == repo_file.path) {
+                failed_files.push(repo_file.clone());
             }
         }
+
         Ok(())
     }
 
+    pub fn has_backup(&self) -> bool {
+        RepoTarget::all_local(&Hachimi::instance().config.load()).iter().any(|t| t.dir_path(BACKUP_EXT).is_dir())
+    }
+
+    /// Swaps the repos that were changed by the last update with their backups
+    /// (so rolling back twice undoes the rollback).
+    pub fn rollback(self: Arc<Self>) {
+        std::thread::spawn(move || {
+            let res = self.rollback_internal();
+            if let Some(mutex) = Gui::instance() {
+                let mut gui = mutex.lock().unwrap();
+                match res {
+                    Ok(_) => gui.show_notification(&t!("notification.tl_rollback_completed")),
+                    Err(e) => {
+                        error!("{}", e);
+                        gui.show_notification(&t!("notification.tl_rollback_failed", reason = e.to_string()));
+                    }
+                }
+            }
+        });
+    }
+
+    fn rollback_internal(&self) -> Result<(), Error> {
+        let Ok(_guard) = self.update_mutex.try_lock() else {
+            return Err(Error::RuntimeError("Update in progress".to_owned()));
+        };
+
+        let hachimi = Hachimi::instance();
+        let targets: Vec<RepoTarget> = RepoTarget::all_local(&hachimi.config.load()).into_iter()
+            .filter(|t| t.dir_path(BACKUP_EXT).is_dir())
+            .collect();
+        if targets.is_empty() {
+            return Err(Error::RuntimeError("No backup available".to_owned()));
+        }
+
+        for target in targets.iter() {
+            swap_paths(&target.dir_path(""), &target.dir_path(BACKUP_EXT))?;
+            swap_paths(&target.cache_path(""), &target.cache_path(BACKUP_EXT))?;
+        }
+
+        // Retrying the failed files would mix the two versions
+        self.failed_update.store(Arc::default());
+        hachimi.load_localized_data();
+        Ok(())
+    }
+
+    pub fn has_failed_files(&self) -> bool {
+        !self.failed_update.load().is_empty()
+    }
+
+    /// Downloads the files that failed in the last update again.
+    pub fn retry_failed(self: Arc<Self>) {
+        let failed_update = self.failed_update.swap(Arc::default());
+        if failed_update.is_empty() {
+            return;
+        }
+        self.new_update.store(failed_update);
+        self.run();
+    }
+
+    /// Installs the main repo from a local repo zip or folder, for when the repo can't be reached.
+    /// The files are verified against the index and the repo cache is updated, so that online updates
+    /// can continue from it.
+    pub fn sideload(self: Arc<Self>, path: PathBuf) {
+        std::thread::spawn(move || {
+            if let Err(e) = self.clone().sideload_internal(&path) {
+                error!("{}", e);
+                self.progress.store(Arc::new(None));
+                if let Some(mutex) = Gui::instance() {
+                    mutex.lock().unwrap().show_notification(&t!("notification.update_failed", reason = e.to_string()));
+                }
+            }
+        });
+    }
+
+    fn sideload_internal(self: Arc<Self>, path: &Path) -> Result<(), Error> {
+        let Ok(_guard) = self.update_mutex.try_lock() else {
+            return Err(Error::RuntimeError("Update in progress".to_owned()));
+        };
+
+        let config = Hachimi::instance().config.load();
+        let target = RepoTarget::main(&config);
+        let index = read_sideload_index(path, target.public_key.as_deref())?;
+        let source = if path.is_dir() {
+            UpdateSource::LocalDir(path.to_owned())
+        }
+        else {
+            UpdateSource::LocalZip(path.to_owned())
+        };
+
+        // Pedantic so that files missing on disk are installed too
+        let Some((update_info, _)) = Self::new_update_info(&config, target, index, source, true)? else {
+            if let Some(mutex) = Gui::instance() {
+                mutex.lock().unwrap().show_notification(&t!("notification.no_tl_updates"));
+            }
+            return Ok(());
+        };
+        drop(config);
+
+        // Any pending update is outdated now
+        self.new_update.store(Arc::default());
+        self.failed_update.store(Arc::default());
+        self.clone().install_updates(&[update_info])
+    }
+
     fn download_incremental(
         self: Arc<Self>,
-        update_info: &UpdateInfo, localized_data_dir: &Path, cached_files: Arc<Mutex<FnvHashMap<String, String>>>
+        update_info: &UpdateInfo, dir: &Path, cached_files: Arc<Mutex<FnvHashMap<String, String>>>,
+        failed_files: Arc<Mutex<Vec<RepoFile>>>
     ) -> Result<usize, Error> {
         let mut jobs_vec = Vec::with_capacity(NUM_THREADS);
         for _ in 0..NUM_THREADS {
@@ -329,31 +1026,32 @@ impl Updater {
         let error_count = Arc::new(AtomicUsize::new(0));
         let total_size = update_info.size;
         for repo_file in update_info.files.iter() {
-            let repo_file_path = repo_file.path.clone();
-            let file_path = repo_file.get_fs_path(localized_data_dir);
-            let url = utils::concat_unix_path(&update_info.base_url, &repo_file.path);
+            let repo_file = repo_file.clone();
+            let file_path = repo_file.get_fs_path(dir);
+            let urls = update_info.file_urls(&repo_file.path);
 
             // Clone the Arcs for the closure
             let jobs = jobs.clone();
-            let file_hash = repo_file.hash.clone();
             let updater = self.clone();
             let current_size = current_size.clone();
             let cached_files = cached_files.clone();
             let error_count = error_count.clone();
+            let failed_files = failed_files.clone();
 
             pool.execute(move || {
                 let mut job = { jobs.lock().unwrap().pop().expect("vacant job in job pool") };
                 
-                let res = job.execute(&file_path, &url, &file_hash, |read_bytes| {
+                let res = job.execute(&file_path, &urls, &repo_file.hash, |read_bytes| {
                     let prev_size = current_size.fetch_add(read_bytes, atomic::Ordering::SeqCst);
                     updater.progress.store(Arc::new(Some(UpdateProgress::new(prev_size + read_bytes, total_size))));
                 });
 
                 match res {
-                    Ok(hash) => { cached_files.lock().unwrap().insert(repo_file_path, hash); },
+                    Ok(hash) => { cached_files.lock().unwrap().insert(repo_file.path, hash); },
                     Err(e) => {
                         error!("{}", e);
                         error_count.fetch_add(1, atomic::Ordering::SeqCst);
+                        failed_files.lock().unwrap().push(repo_file);
                     }
                 }
 
@@ -370,41 +1068,59 @@ impl Updater {
 
     fn download_zip(
         self: Arc<Self>,
-        update_info: &UpdateInfo, localized_data_dir: &Path, cached_files: Arc<Mutex<FnvHashMap<String, String>>>
+        update_info: &UpdateInfo, dir: &Path, cached_files: Arc<Mutex<FnvHashMap<String, String>>>,
+        failed_files: Arc<Mutex<Vec<RepoFile>>>
     ) -> Result<usize, Error> {
-        let mut cached_files = cached_files.lock().unwrap();
         let mut error_count = 0;
-        let zip_path = localized_data_dir.join(".tmp.zip");
+        // Kept outside of the repo's dir (which gets wiped for new repos) so that it can be resumed,
+        // named after the url and the index's files so that a different repo's zip (or an older
+        // version of the same zip) won't be resumed by accident
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(update_info.zip_url.as_bytes());
+        for repo_file in update_info.index_files.iter() {
+            hasher.update(b"\0");
+            hasher.update(repo_file.path.as_bytes());
+            hasher.update(b"\0");
+            hasher.update(repo_file.hash.as_bytes());
+        }
+        let zip_hash = hasher.finalize().to_hex();
+        let zip_path = Hachimi::instance().get_data_path(format!(".tl_repo_{}.zip", &zip_hash[..16]));
+
+        let mut buffer = [0u8; CHUNK_SIZE];
+        let mut fake_progress = 0;
+        http::download_file_resumable(&http::agent(), &update_info.zip_urls(), &zip_path, &mut buffer, |downloaded, total| {
+            let progress = if let Some(len) = total {
+                UpdateProgress::new(downloaded, len)
+            }
+            else {
+                fake_progress += 1;
+                UpdateProgress::new(fake_progress, 100000)
+            };
+            self.progress.store(Arc::new(Some(progress)));
+        })?;
+
+        let res = self.extract_zip(update_info, &zip_path, dir, &cached_files, &failed_files, &mut buffer);
+
+        // Also removed if the extraction failed since the zip is most likely broken
+        if let Err(e) = fs::remove_file(&zip_path) {
+            error!("Failed to remove '{}': {}", zip_path.display(), e);
+            error_count += 1;
+        }
+
+        res?;
+        Ok(error_count)
+    }
+
+    fn extract_zip(
+        &self,
+        update_info: &UpdateInfo, zip_path: &Path, dir: &Path, cached_files: &Mutex<FnvHashMap<String, String>>,
+        failed_files: &Mutex<Vec<RepoFile>>, buffer: &mut [u8]
+    ) -> Result<(), Error> {
+        let mut cached_files = cached_files.lock().unwrap();
         let sync_pool = ThreadPool::new(NUM_THREADS);
 
         { // block that drops the file objects so we can delete the temp file later
-            let mut zip_file = fs::File::options()
-                .read(true)
-                .write(true)
-                .create(true)
-                .truncate(true)
-                .open(&zip_path)?;
-
-            let res = ureq::get(&update_info.zip_url).call()?;
-            let content_length = res.header("Content-Length")
-                .map(|s| s.parse::<usize>().ok())
-                .unwrap_or_default();
-            let mut buffer = [0u8; CHUNK_SIZE];
-            let mut downloaded = 0;
-            http::download_file_buffered(res, &mut zip_file, &mut buffer, |bytes| {
-                let progress = if let Some(len) = &content_length {
-                    downloaded += bytes.len();
-                    UpdateProgress::new(downloaded, *len)
-                }
-                else {
-                    // fake progress
-                    downloaded += 1;
-                    UpdateProgress::new(downloaded, 100000)
-                };
-                self.progress.store(Arc::new(Some(progress)));
-            })?;
-            zip_file.sync_data()?;
-
+            let zip_file = fs::File::open(zip_path)?;
             let mut zip_archive = zip::ZipArchive::new(zip_file)?;
             let mut hasher = blake3::Hasher::new();
             let mut current_bytes = 0;
@@ -413,15 +1129,21 @@ impl Updater {
                 let mut archive_file = match zip_archive.by_name(&archive_path) {
                     Ok(v) => v,
                     Err(_) => {
+                        // Could still be downloaded on its own
                         error!("File not found in zip: {}", archive_path);
+                        failed_files.lock().unwrap().push(repo_file.clone());
                         continue;
                     }
                 };
 
-                let path = repo_file.get_fs_path(localized_data_dir);
+                let path = repo_file.get_fs_path(dir);
                 if let Some(parent) = Path::new(&path).parent() {
                     fs::create_dir_all(parent)?;
                 }
+                // Might be hard linked to the current version's file
+                if path.exists() {
+                    fs::remove_file(&path)?;
+                }
                 let mut file = fs::File::create(&path)?;
 
                 let mut buffer_pos = 0usize;
@@ -475,9 +1197,40 @@ impl Updater {
         // Wait for the sync pool to finish
         sync_pool.join();
 
-        if let Err(e) = fs::remove_file(&zip_path) {
-            error!("Failed to remove '{}': {}", zip_path.display(), e);
-            error_count += 1;
+        Ok(())
+    }
+
+    // Returns the amount of files that couldn't be copied
+    fn copy_files(
+        &self,
+        update_info: &UpdateInfo, src_dir: &Path, dir: &Path, cached_files: &Mutex<FnvHashMap<String, String>>,
+        failed_files: &Mutex<Vec<RepoFile>>
+    ) -> Result<usize, Error> {
+        let src_dir = src_dir.join(&update_info.zip_dir);
+        let mut error_count = 0;
+        let mut current_size = 0;
+        for repo_file in update_info.files.iter() {
+            let src_path = repo_file.get_fs_path(&src_dir);
+            let path = repo_file.get_fs_path(dir);
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            // Might be hard linked to the current version's file
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+
+            if let Err(e) = fs::copy(&src_path, &path) {
+                error!("Failed to copy '{}': {}", src_path.display(), e);
+                error_count += 1;
+                failed_files.lock().unwrap().push(repo_file.clone());
+                continue;
+            }
+            // The hash is checked by verify_files
+            cached_files.lock().unwrap().insert(repo_file.path.clone(), repo_file.hash.clone());
+
+            current_size += repo_file.size;
+            self.progress.store(Arc::new(Some(UpdateProgress::new(current_size, update_info.size))));
         }
 
         Ok(error_count)
@@ -486,4 +1239,47 @@ impl Updater {
     pub fn progress(&self) -> Option<UpdateProgress> {
         (**self.progress.load()).clone()
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use super::*;
+    use crate::core::http::test_server;
+
+    const INDEX: &str = r#"{"base_url": "https://example.com/repo", "zip_url": "https://example.com/repo.zip", "zip_dir": "repo", "files": []}"#;
+
+    #[test]
+    fn index_fails_over() {
+        let signing_key = SigningKey::from_bytes(&[7; 32]);
+        let public_key = BASE64_STANDARD.encode(signing_key.verifying_key().as_bytes());
+        let signature = BASE64_STANDARD.encode(signing_key.sign(INDEX.as_bytes()).to_bytes());
+
+        let (down_url, _) = test_server::spawn(1, |_, stream| test_server::respond(stream, "503 Service Unavailable", &[], b""));
+        let (mirror_url, mirror) = test_server::spawn(2, move |request, stream| {
+            let body = if request.path.ends_with(SIGNATURE_EXT) { signature.as_bytes() } else { INDEX.as_bytes() };
+            test_server::respond(stream, "200 OK", &[], body);
+        });
+
+        let urls = [down_url + "/index.json", mirror_url + "/index.json"];
+        let index = get_repo_index(&ureq::Agent::new(), &urls, Some(&public_key)).unwrap();
+        assert_eq!(index.base_url, "https://example.com/repo");
+
+        // The signature comes from the same mirror as the index
+        let paths: Vec<String> = mirror.join().unwrap().into_iter().map(|r| r.path).collect();
+        assert_eq!(paths, ["/index.json", "/index.json.sig"]);
+    }
+
+    #[test]
+    fn index_signature_is_not_failed_over() {
+        let (url, _) = test_server::spawn(2, |request, stream| {
+            let status = if request.path.ends_with(SIGNATURE_EXT) { "404 Not Found" } else { "200 OK" };
+            test_server::respond(stream, status, &[], INDEX.as_bytes());
+        });
+
+        let urls = [url + "/index.json", "http://127.0.0.1:1/index.json".to_owned()];
+        let public_key = BASE64_STANDARD.encode([0u8; 32]);
+        let res = get_repo_index(&ureq::Agent::new(), &urls, Some(&public_key));
+        assert!(matches!(res, Err(Error::InvalidSignature(_))));
+    }
+}
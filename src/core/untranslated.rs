@@ -0,0 +1,105 @@
+use std::{hash::Hash, path::PathBuf, sync::{atomic::{self, AtomicBool}, Mutex}, time::{SystemTime, UNIX_EPOCH}};
+
+use fnv::FnvHashMap;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+
+use super::{utils, Error, Hachimi};
+
+pub const REPORT_FILENAME: &str = "untranslated_report.json";
+
+// Collects the strings that reached the game without a translation (translator mode only)
+static ENTRIES: Lazy<Mutex<FnvHashMap<Source, Entry>>> = Lazy::new(|| Mutex::default());
+// Mirrors config.translator_mode, checked on hot paths so it shouldn't touch the config
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Serialize, Clone, PartialEq, Eq, Hash)]
+#[serde(tag = "type")]
+pub enum Source {
+    // localize_dict
+    TextId { name: String },
+    // hashed_dict
+    Hashed { hash: u64 },
+    // text_data_dict
+    TextData { category: i32, index: i32 },
+    // character_system_text_dict
+    CharacterSystemText { character_id: i32, voice_id: i32 },
+    // sql_tables
+    SqlTable { table: String, key: String }
+}
+
+struct Entry {
+    text: String,
+    hits: u64,
+    first_seen: u64
+}
+
+#[derive(Serialize)]
+struct ReportEntry<'a> {
+    source: &'a Source,
+    text: &'a str,
+    hits: u64,
+    first_seen: u64
+}
+
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, atomic::Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(atomic::Ordering::Relaxed)
+}
+
+/// Records a hit for an untranslated string. `text` is only evaluated the first time the source is seen.
+pub fn record(source: Source, text: impl FnOnce() -> String) {
+    if !is_enabled() {
+        return;
+    }
+
+    let mut entries = ENTRIES.lock().unwrap();
+    if let Some(entry) = entries.get_mut(&source) {
+        entry.hits += 1;
+        return;
+    }
+
+    let first_seen = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    entries.insert(source, Entry { text: text(), hits: 1, first_seen });
+}
+
+/// Hashed text comes in already translated most of the time (from other dicts),
+/// so only strings that still look like Japanese are worth recording.
+pub fn record_hashed(hash: u64, text: &str) {
+    if is_enabled() && text.chars().any(is_japanese_char) {
+        record(Source::Hashed { hash }, || text.to_owned());
+    }
+}
+
+fn is_japanese_char(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}' | // Hiragana, Katakana
+        '\u{4E00}'..='\u{9FFF}' | // CJK Unified Ideographs
+        '\u{FF66}'..='\u{FF9F}'   // Halfwidth Katakana
+    )
+}
+
+pub fn count() -> usize {
+    ENTRIES.lock().unwrap().len()
+}
+
+/// Writes the report to the data dir, oldest entries first. Returns the report's path.
+pub fn save_report() -> Result<PathBuf, Error> {
+    let entries = ENTRIES.lock().unwrap();
+    let mut report: Vec<ReportEntry> = entries.iter()
+        .map(|(source, entry)| ReportEntry {
+            source,
+            text: &entry.text,
+            hits: entry.hits,
+            first_seen: entry.first_seen
+        })
+        .collect();
+    report.sort_by_key(|e| e.first_seen);
+
+    let path = Hachimi::instance().get_data_path(REPORT_FILENAME);
+    utils::write_json_file(&report, &path)?;
+    Ok(path)
+}
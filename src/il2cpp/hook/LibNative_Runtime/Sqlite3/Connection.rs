@@ -3,13 +3,15 @@ use std::sync::Mutex;
 use fnv::FnvHashMap;
 use once_cell::sync::Lazy;
 use sqlparser::{
-    ast::BinaryOperator,
     dialect::SQLiteDialect,
     keywords::Keyword,
     parser::Parser
 };
 
-use crate::il2cpp::{ext::Il2CppStringExt, sql::{self, ExprExt, SelectExt, SelectItemExt}, symbols::get_method_addr, types::*};
+use crate::{
+    core::Hachimi,
+    il2cpp::{ext::Il2CppStringExt, sql::{self, SelectExt, SelectItemExt}, symbols::get_method_addr, types::*}
+};
 
 pub static SELECT_QUERIES: Lazy<Mutex<FnvHashMap<usize, Box<dyn sql::SelectQueryState + Send + Sync>>>> =
     Lazy::new(|| Mutex::new(FnvHashMap::default()));
@@ -48,7 +50,13 @@ fn parse_query(query: *mut Il2CppObject, sql: *const Il2CppString) {
             "character_system_text" => Box::new(sql::CharacterSystemTextQuery::default()),
             "race_jikkyo_comment" => Box::new(sql::RaceJikkyoCommentQuery::default()),
             "race_jikkyo_message" => Box::new(sql::RaceJikkyoMessageQuery::default()),
-            _ => return
+            _ => {
+                let localized_data = Hachimi::instance().localized_data.load();
+                let Some(table) = localized_data.config.sql_tables.iter().find(|t| &t.table == table_name) else {
+                    return;
+                };
+                Box::new(sql::ConfigTableQuery::new(table))
+            }
         };
 
         // Add columns
@@ -60,20 +68,9 @@ fn parse_query(query: *mut Il2CppObject, sql: *const Il2CppString) {
             }
         }
 
-        // Add params
-        i = 1; // index starts at 1
-        if let Some(selection) = select.selection {
-            // this should visit them in order (column1 = ? AND column2 = ? ...)
-            for expr in selection.binary_op_iter() {
-                if *expr.op != BinaryOperator::Eq { continue; }
-
-                if let Some(name) = expr.left.get_ident_value() {
-                    if expr.right.is_placeholder_value() {
-                        query_state.add_param(i, name);
-                        i += 1;
-                    }
-                }
-            }
+        // Add params and literals
+        if let Some(selection) = &select.selection {
+            sql::SelectionVisitor::new(query_state.as_mut()).visit(selection);
         }
 
         // Add query state
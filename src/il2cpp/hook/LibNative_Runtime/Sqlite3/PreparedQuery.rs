@@ -1,4 +1,4 @@
-use crate::il2cpp::{symbols::get_method_addr, types::*};
+use crate::il2cpp::{ext::Il2CppStringExt, sql::Value, symbols::get_method_addr, types::*};
 
 use super::Connection::SELECT_QUERIES;
 
@@ -10,15 +10,32 @@ use super::Connection::SELECT_QUERIES;
 type BindIntFn = extern "C" fn(this: *mut Il2CppObject, idx: i32, value: i32) -> bool;
 extern "C" fn BindInt(this: *mut Il2CppObject, idx: i32, value: i32) -> bool {
     if let Some(query) = SELECT_QUERIES.lock().unwrap().get_mut(&(this as usize)) {
-        query.bind_int(idx, value);
+        query.bind(idx, &Value::Int(value));
     }
     get_orig_fn!(BindInt, BindIntFn)(this, idx, value)
 }
 
+/**
+ * LibNative.Sqlite3::PreparedQuery::BindText
+ * Binds a string value to a parameter.
+ * idx starts from 1
+ */
+type BindTextFn = extern "C" fn(this: *mut Il2CppObject, idx: i32, value: *mut Il2CppString) -> bool;
+extern "C" fn BindText(this: *mut Il2CppObject, idx: i32, value: *mut Il2CppString) -> bool {
+    if !value.is_null() {
+        if let Some(query) = SELECT_QUERIES.lock().unwrap().get_mut(&(this as usize)) {
+            query.bind(idx, &Value::Text(unsafe { (*value).as_utf16str().to_string() }));
+        }
+    }
+    get_orig_fn!(BindText, BindTextFn)(this, idx, value)
+}
+
 pub fn init(LibNative_Runtime: *const Il2CppImage) {
     get_class_or_return!(LibNative_Runtime, "LibNative.Sqlite3", PreparedQuery);
 
     let BindInt_addr = get_method_addr(PreparedQuery, c"BindInt", 2);
+    let BindText_addr = get_method_addr(PreparedQuery, c"BindText", 2);
 
     new_hook!(BindInt_addr, BindInt);
-}
\ No newline at end of file
+    new_hook!(BindText_addr, BindText);
+}
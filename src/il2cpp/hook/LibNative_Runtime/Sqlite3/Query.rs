@@ -10,6 +10,10 @@ extern "C" fn GetText(this: *mut Il2CppObject, idx: i32) -> *mut Il2CppString {
     get_orig_fn!(GetText, GetTextFn)(this, idx)
 }
 
+pub fn GetText_orig(this: *mut Il2CppObject, idx: i32) -> *mut Il2CppString {
+    get_orig_fn!(GetText, GetTextFn)(this, idx)
+}
+
 type DisposeFn = extern "C" fn(this: *mut Il2CppObject);
 extern "C" fn Dispose(this: *mut Il2CppObject) {
     SELECT_QUERIES.lock().unwrap().remove(&(this as usize));
@@ -1,11 +1,12 @@
 use std::path::Path;
 
-use fnv::FnvHashMap;
-use serde::Deserialize;
 use widestring::Utf16Str;
 
 use crate::{
-    core::{ext::Utf16StringExt, hachimi::AssetInfo, Hachimi},
+    core::{
+        asset_dicts::{AnMotionParameterData, AnRootData, AnTextParameterData}, asset_skeleton::{self, SkeletonAssetInfo},
+        ext::Utf16StringExt, hachimi::AssetInfo, Hachimi
+    },
     il2cpp::{
         api::{il2cpp_class_get_type, il2cpp_type_get_object}, ext::{Il2CppStringExt, StringExt}, hook::{UnityEngine_AssetBundleModule::AssetBundle, UnityEngine_CoreModule::Object}, symbols::{get_field_from_name, get_field_object_value, IList}, types::*, utils::replace_texture_with_diff
     }
@@ -38,32 +39,6 @@ pub fn get__topObject(this: *mut Il2CppObject) -> *mut Il2CppObject {
     get_field_object_value(this, unsafe { _TOPOBJECT_FIELD })
 }
 
-#[derive(Deserialize)]
-pub struct AnRootData {
-    #[serde(default)]
-    motion_parameter_list: FnvHashMap<i32, AnMotionParameterData>
-}
-
-#[derive(Deserialize)]
-struct AnMotionParameterData {
-    #[serde(default)]
-    text_param_list: FnvHashMap<i32, AnTextParameterData>
-}
-
-#[derive(Deserialize)]
-struct AnObjectParameterBaseData {
-    position_offset: Option<Vector3_t>,
-    scale: Option<Vector3_t>
-}
-
-#[derive(Deserialize)]
-struct AnTextParameterData {
-    text: Option<String>,
-
-    #[serde(flatten)]
-    base: AnObjectParameterBaseData
-}
-
 pub fn on_LoadAsset(bundle: *mut Il2CppObject, this: *mut Il2CppObject, name: &Utf16Str) {
     // SAFETY: The asset path has been checked prior to this being called in GameObject::on_LoadAsset
     let base_path = name[AssetBundle::ASSET_PATH_PREFIX.len()..].path_basename();
@@ -74,9 +49,53 @@ pub fn on_LoadAsset(bundle: *mut Il2CppObject, this: *mut Il2CppObject, name: &U
         return;
     }
 
+    if asset_info.data.is_none() {
+        asset_skeleton::save(&(base_path.to_string() + ".json"), || {
+            generate_skeleton_data(this).map(|data| SkeletonAssetInfo { data })
+        });
+    }
+
     patch_asset(this, asset_info.data.as_ref());
 }
 
+// Returns None if there's no text to translate
+pub fn generate_skeleton_data(this: *mut Il2CppObject) -> Option<AnRootData> {
+    let root_param = get__parameter(this);
+    let motion_param_group = AnRootParameter::get__motionParameterGroup(root_param);
+    let motion_param_list = IList::new(AnMotionParameterGroup::get__motionParameterList(motion_param_group))?;
+
+    let mut data = AnRootData::default();
+    for (i, motion_param) in motion_param_list.iter().enumerate() {
+        let Some(text_param_list) = IList::new(AnMotionParameter::get__textParamList(motion_param)) else {
+            continue;
+        };
+
+        let mut motion_param_data = AnMotionParameterData::default();
+        for (j, text_param) in text_param_list.iter().enumerate() {
+            let text = AnTextParameter::get__text(text_param);
+            if text.is_null() || unsafe { (*text).length == 0 } {
+                continue;
+            }
+
+            motion_param_data.text_param_list.insert(j as i32, AnTextParameterData {
+                orig_text: Some(unsafe { (*text).as_utf16str().to_string() }),
+                ..Default::default()
+            });
+        }
+
+        if !motion_param_data.text_param_list.is_empty() {
+            data.motion_parameter_list.insert(i as i32, motion_param_data);
+        }
+    }
+
+    if data.motion_parameter_list.is_empty() {
+        None
+    }
+    else {
+        Some(data)
+    }
+}
+
 pub fn patch_asset(this: *mut Il2CppObject, data_opt: Option<&AnRootData>) {
     /*** Texture set replacement ***/
     let param_group = get__meshParameterGroup(this);
@@ -146,12 +165,12 @@ pub fn patch_asset(this: *mut Il2CppObject, data_opt: Option<&AnRootData>) {
                     AnTextParameter::set__text(text_param, text.to_il2cpp_string());
                 }
 
-                if let Some(position_offset) = &text_param_data.base.position_offset {
-                    AnObjectParameterBase::set__positionOffset(text_param, position_offset);
+                if let Some(v) = text_param_data.base.position_offset {
+                    AnObjectParameterBase::set__positionOffset(text_param, &Vector3_t { x: v.x, y: v.y, z: v.z });
                 }
                                                                     
-                if let Some(scale) = &text_param_data.base.scale {
-                    AnObjectParameterBase::set__scale(text_param, scale);
+                if let Some(v) = text_param_data.base.scale {
+                    AnObjectParameterBase::set__scale(text_param, &Vector3_t { x: v.x, y: v.y, z: v.z });
                 }
             }
         }
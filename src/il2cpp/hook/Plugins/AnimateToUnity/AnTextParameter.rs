@@ -1,6 +1,10 @@
-use crate::il2cpp::{symbols::{get_field_from_name, set_field_object_value}, types::*};
+use crate::il2cpp::{symbols::{get_field_from_name, get_field_object_value, set_field_object_value}, types::*};
 
 static mut _TEXT_FIELD: *mut FieldInfo = 0 as _;
+pub fn get__text(this: *mut Il2CppObject) -> *mut Il2CppString {
+    get_field_object_value(this, unsafe { _TEXT_FIELD })
+}
+
 pub fn set__text(this: *mut Il2CppObject, value: *mut Il2CppString) {
     set_field_object_value(this, unsafe { _TEXT_FIELD }, value);
 }
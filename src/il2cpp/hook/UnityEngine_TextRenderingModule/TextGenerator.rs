@@ -1,6 +1,6 @@
 use std::ops::Not;
 
-use crate::{core::{template, Hachimi}, il2cpp::{ext::{Il2CppStringExt, StringExt}, symbols::get_method_addr, types::*}};
+use crate::{core::{pattern_dict, template, template_filters, untranslated, Hachimi}, il2cpp::{ext::{Il2CppStringExt, StringExt}, symbols::get_method_addr, types::*}};
 
 type PopulateWithErrorsFn = extern "C" fn(
     this: *mut Il2CppObject, str: *mut Il2CppString,
@@ -20,13 +20,20 @@ extern "C" fn PopulateWithErrors(
     {
         orig_fn(this, text.to_il2cpp_string(), settings, context)
     }
+    else if let Some(new_str) = apply_pattern_dict(&localized_data.pattern_dict, str_, &mut settings) {
+        orig_fn(this, new_str, settings, context)
+    }
     else if !localized_data.localize_dict.is_empty() || !localized_data.text_data_dict.is_empty() {
         let str = unsafe { (*str_).as_utf16str() };
+        if untranslated::is_enabled() {
+            untranslated::record_hashed(unsafe { (*str_).hash() }, &str.to_string());
+        }
 
         // Only try to evaluate a template if it looks like one
         let new_str = if str.as_slice().contains(&36) { // 36 = dollar sign ($)
             let mut context = TemplateContext {
-                settings: &mut settings
+                settings: &mut settings,
+                captures: None
             };
             Hachimi::instance().template_parser
                 .eval_with_context(&str.to_string(), &mut context)
@@ -38,12 +45,32 @@ extern "C" fn PopulateWithErrors(
         orig_fn(this, new_str, settings, context)
     }
     else {
+        if untranslated::is_enabled() {
+            untranslated::record_hashed(unsafe { (*str_).hash() }, &unsafe { (*str_).as_utf16str() }.to_string());
+        }
         orig_fn(this, str_, settings, context)
     }
 }
 
+fn apply_pattern_dict(
+    pattern_dict: &pattern_dict::PatternDict, str_: *mut Il2CppString, settings: &mut TextGenerationSettings_t
+) -> Option<*mut Il2CppString> {
+    if pattern_dict.is_empty() {
+        return None;
+    }
+
+    let str = unsafe { (*str_).as_utf16str() }.to_string();
+    let (text, captures) = pattern_dict.find(&str)?;
+    let mut context = TemplateContext {
+        settings,
+        captures: Some(&captures)
+    };
+    Some(Hachimi::instance().template_parser.eval_with_context(text, &mut context).to_il2cpp_string())
+}
+
 struct TemplateContext<'a> {
-    settings: &'a mut TextGenerationSettings_t
+    settings: &'a mut TextGenerationSettings_t,
+    captures: Option<&'a regex::Captures<'a>>
 }
 
 impl<'a> template::Context for TemplateContext<'a> {
@@ -112,17 +139,18 @@ impl<'a> template::Context for TemplateContext<'a> {
 
         Some(String::new())
     }
+
+    fn on_var_eval(&mut self, name: &str) -> Option<template::Token> {
+        pattern_dict::capture_var(self.captures?, name)
+    }
 }
 
 // Context that ignores TextGenerator filters
 pub struct IgnoreTGFiltersContext();
 
 impl template::Context for IgnoreTGFiltersContext {
-    fn on_filter_eval(&mut self, _name: &str, _args: &[template::Token]) -> Option<String> {
-        match _name {
-            "nb" | "anchor" | "scale" | "ho" | "vo" => Some(String::new()),
-            _ => None
-        }
+    fn on_filter_eval(&mut self, name: &str, _args: &[template::Token]) -> Option<String> {
+        template_filters::TEXT_GENERATOR_FILTERS.contains(&name).then(String::new)
     }
 }
 
@@ -1,8 +1,10 @@
-use serde::Deserialize;
 use widestring::Utf16Str;
 
 use crate::{
-    core::{ext::Utf16StringExt, hachimi::AssetInfo, Hachimi},
+    core::{
+        asset_dicts::FlashActionPlayerData, asset_skeleton::{self, SkeletonAssetInfo}, ext::Utf16StringExt,
+        hachimi::AssetInfo, Hachimi
+    },
     il2cpp::{
         api::{il2cpp_class_get_type, il2cpp_type_get_object},
         hook::{
@@ -25,11 +27,6 @@ pub fn get__flashPrefab(this: *mut Il2CppObject) -> *mut Il2CppObject {
     get_field_object_value(this, unsafe { _FLASHPREFAB_FIELD })
 }
 
-#[derive(Deserialize)]
-pub struct FlashActionPlayerData {
-    an_root: Option<AnRoot::AnRootData>
-}
-
 pub fn on_LoadAsset(bundle: *mut Il2CppObject, this: *mut Il2CppObject, name: &Utf16Str) {
     // SAFETY: The asset path has been checked prior to this being called in GameObject::on_LoadAsset
     let base_path = name[AssetBundle::ASSET_PATH_PREFIX.len()..].path_basename();
@@ -47,6 +44,12 @@ pub fn on_LoadAsset(bundle: *mut Il2CppObject, this: *mut Il2CppObject, name: &U
 
     let root = GameObject::GetComponentInChildren(flash_prefab, AnRoot::type_object(), false);
     if !root.is_null() {
+        if asset_info.data.is_none() {
+            asset_skeleton::save(&(base_path.to_string() + ".json"), || {
+                AnRoot::generate_skeleton_data(root)
+                    .map(|an_root| SkeletonAssetInfo { data: FlashActionPlayerData { an_root: Some(an_root) } })
+            });
+        }
         AnRoot::patch_asset(root, asset_info.data.map(|d| d.an_root).unwrap_or_default().as_ref());
     }
 }
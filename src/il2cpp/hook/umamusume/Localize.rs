@@ -4,7 +4,7 @@ use fnv::FnvHashMap;
 use once_cell::unsync::Lazy;
 
 use crate::{
-    core::{game::Region, utils, Hachimi, SugoiClient},
+    core::{game::Region, mt, untranslated, utils, Hachimi},
     il2cpp::{ext::{Il2CppStringExt, StringExt}, symbols::{get_method_overload_addr, unbox}, types::*}
 };
 
@@ -45,11 +45,14 @@ pub extern "C" fn Get(id: i32) -> *mut Il2CppString {
         let str = get_orig_fn!(Get, GetFn)(id);
         if Hachimi::instance().config.load().translator_mode && id != 1109 && id != 1032 {
             // 1109 and 1032 seems to be debugging strings (they're annoying)
-            utils::print_json_entry(name, unsafe { &(*str).as_utf16str().to_string() });
+            let text = if str.is_null() { String::new() } else { unsafe { (*str).as_utf16str().to_string() } };
+            utils::print_json_entry(name, &text);
+            untranslated::record(untranslated::Source::TextId { name: name.clone() }, || text);
         }
         if hachimi.config.load().auto_translate_localize && !str.is_null() && unsafe { (*str).length > 0 } {
+            // Returns the original text until the translation is ready
             let s = unsafe { (*str).as_utf16str().to_string() };
-            if let Ok(res) = SugoiClient::instance().translate_one(s) {
+            if let Some(res) = mt::queue::translate_or_enqueue(&s, Some(name)) {
                 return res.to_il2cpp_string();
             }
         }
@@ -1,9 +1,9 @@
-use std::path::Path;
+use std::{collections::BTreeMap, path::Path};
 
 use fnv::FnvHashMap;
 
 use crate::{
-    core::{ext::Utf16StringExt, Hachimi},
+    core::{asset_dicts::{LyricsDict, SourceHashedText}, asset_skeleton, ext::Utf16StringExt, source_hash, Hachimi},
     il2cpp::{
         ext::{Il2CppStringExt, StringExt},
         symbols::{get_field_from_name, get_field_object_value, get_method_addr, Array, Dictionary},
@@ -22,6 +22,10 @@ struct LyricsData {
     lyrics: *mut Il2CppString
 }
 
+fn get_lyrics_string(lyrics_data: &LyricsData) -> String {
+    if lyrics_data.lyrics.is_null() { String::new() } else { unsafe { (*lyrics_data.lyrics).as_utf16str().to_string() } }
+}
+
 type LoadLyricsFn = extern "C" fn(this: *mut Il2CppObject, id: i32, path: *mut Il2CppString) -> bool;
 extern "C" fn LoadLyrics(this: *mut Il2CppObject, id: i32, path: *mut Il2CppString) -> bool {
     if !get_orig_fn!(LoadLyrics, LoadLyricsFn)(this, id, path) {
@@ -33,24 +37,45 @@ extern "C" fn LoadLyrics(this: *mut Il2CppObject, id: i32, path: *mut Il2CppStri
 
     let mut dict_path = Path::new("lyrics").join(path_str.path_filename().to_string());
     dict_path.set_extension("json");
+    let lyrics_data_dict = get__lyricsDataDic(this);
+    let Some(lyrics_data_array) = lyrics_data_dict.get(&id) else {
+        return true;
+    };
+
     let localized_data = Hachimi::instance().localized_data.load();
-    let Some(dict): Option<FnvHashMap<i32, String>> = localized_data.load_assets_dict(Some(&dict_path)) else {
+    let Some(dict): Option<LyricsDict> = localized_data.load_assets_dict(Some(&dict_path)) else {
+        // BTreeMap so the lyrics are written in order
+        asset_skeleton::save(&dict_path.to_string_lossy(), || Some(
+            unsafe { lyrics_data_array.as_slice().iter() }
+                .map(|lyrics_data| {
+                    let orig_text = get_lyrics_string(lyrics_data);
+                    ((lyrics_data.time * 1000.0).round() as i32, SourceHashedText::Hashed {
+                        // Empty texts blank the line, start out with the original text instead
+                        text: orig_text.clone(),
+                        source_hash: Some(source_hash::compute(&orig_text)),
+                        orig_text: Some(orig_text)
+                    })
+                })
+                .collect::<BTreeMap<i32, SourceHashedText>>()
+        ));
         return true;
     };
     // dont let pbork interactive know about this
-    let secs_dict: FnvHashMap<i32, String> = dict.into_iter()
-        .map(|(time, lyrics)| unsafe { (std::mem::transmute(time as f32 / 1000.0), lyrics) })
+    let secs_dict: FnvHashMap<i32, (i32, SourceHashedText)> = dict.into_iter()
+        .map(|(time, lyrics)| unsafe { (std::mem::transmute(time as f32 / 1000.0), (time, lyrics)) })
         .collect();
 
-    let lyrics_data_dict = get__lyricsDataDic(this);
-    let Some(lyrics_data_array) = lyrics_data_dict.get(&id) else {
-        return true;
-    };
     for lyrics_data in unsafe { lyrics_data_array.as_slice().iter_mut() } {
         // transmute to an i32 so we can do an exact match lookup in the map
         let time: i32 = unsafe { std::mem::transmute(lyrics_data.time) };
-        if let Some(text) = secs_dict.get(&time) {
-            lyrics_data.lyrics = text.to_il2cpp_string();
+        let Some((time_ms, text)) = secs_dict.get(&time) else {
+            continue;
+        };
+        let source_ok = source_hash::check(
+            &dict_path.to_string_lossy(), text.source_hash(), || get_lyrics_string(lyrics_data), || time_ms.to_string()
+        );
+        if source_ok {
+            lyrics_data.lyrics = text.text().to_il2cpp_string();
         }
     }
 
@@ -27,6 +27,15 @@ extern "C" fn GetMonthTextByTurn(turn_set_id: i32, turn: i32) -> *mut Il2CppStri
                     _ => None
                 }
             }
+
+            // $(select ${half} 1 'Early' 'Late')
+            fn on_var_eval(&mut self, name: &str) -> Option<template::Token> {
+                match name {
+                    "month" => Some(template::Token::NumberLit(SingleModeTurn::get_Month(self.turn) as f64)),
+                    "half" => Some(template::Token::NumberLit(SingleModeTurn::get_Half(self.turn) as f64)),
+                    _ => None
+                }
+            }
         }
 
         let turn = GetMasterTurn(turn_set_id, turn);
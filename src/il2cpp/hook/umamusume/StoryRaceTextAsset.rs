@@ -3,9 +3,9 @@ use std::ptr::null_mut;
 use widestring::Utf16Str;
 
 use crate::{
-    core::{ext::Utf16StringExt, Hachimi},
+    core::{asset_dicts::{SourceHashedText, StoryRaceTextDict}, asset_skeleton, ext::Utf16StringExt, source_hash, Hachimi},
     il2cpp::{
-        ext::{Il2CppObjectExt, StringExt}, hook::UnityEngine_AssetBundleModule::AssetBundle::ASSET_PATH_PREFIX, symbols::{get_field_from_name, get_field_object_value, set_field_object_value, Array}, types::*
+        ext::{Il2CppObjectExt, Il2CppStringExt, StringExt}, hook::UnityEngine_AssetBundleModule::AssetBundle::ASSET_PATH_PREFIX, symbols::{get_field_from_name, get_field_object_value, set_field_object_value, Array}, types::*
     }
 };
 
@@ -19,17 +19,30 @@ fn get_textData(this: *mut Il2CppObject) -> Array {
     Array::from(get_field_object_value(this, unsafe { TEXT_DATA_FIELD }))
 }
 
-// I'd move this out to its own module, but there's only a single function we need rn sooooo...
+// I'd move this out to its own module, but there's only a couple of functions we need rn sooooo...
 static mut KEY_TEXT_FIELD: *mut FieldInfo = null_mut();
-fn Key_set_text(key: *mut Il2CppObject, value: *mut Il2CppString) {
+fn Key_text_field(key: *mut Il2CppObject) -> *mut FieldInfo {
     unsafe {
         if KEY_TEXT_FIELD.is_null() {
             KEY_TEXT_FIELD = get_field_from_name((*key).klass(), c"text");
         }
-        set_field_object_value(key, KEY_TEXT_FIELD, value);
+        KEY_TEXT_FIELD
     }
 }
 
+fn Key_get_text(key: *mut Il2CppObject) -> *mut Il2CppString {
+    get_field_object_value(key, Key_text_field(key))
+}
+
+fn Key_set_text(key: *mut Il2CppObject, value: *mut Il2CppString) {
+    set_field_object_value(key, Key_text_field(key), value);
+}
+
+fn Key_get_text_string(key: *mut Il2CppObject) -> String {
+    let text = Key_get_text(key);
+    if text.is_null() { String::new() } else { unsafe { (*text).as_utf16str().to_string() } }
+}
+
 // hook::UnityEngine_AssetBundleModule::AssetBundle
 // name: assets/_gallopresources/bundle/resources/race/storyrace/text/storyrace_xxxxxxxxx.asset
 pub fn on_LoadAsset(_bundle: *mut Il2CppObject, this: *mut Il2CppObject, name: &Utf16Str) {
@@ -41,14 +54,30 @@ pub fn on_LoadAsset(_bundle: *mut Il2CppObject, this: *mut Il2CppObject, name: &
     let base_path = name[ASSET_PATH_PREFIX.len()..].path_basename();
     let dict_path = base_path.to_string() + ".json";
     let localized_data = Hachimi::instance().localized_data.load();
-    let Some(dict): Option<Vec<String>> = localized_data.load_assets_dict(Some(&dict_path)) else {
+    let text_data = get_textData(this);
+    let Some(dict): Option<StoryRaceTextDict> = localized_data.load_assets_dict(Some(&dict_path)) else {
+        asset_skeleton::save(&dict_path, || Some(
+            unsafe { text_data.as_slice().iter() }
+                .map(|key| {
+                    let orig_text = Key_get_text_string(*key);
+                    SourceHashedText::Hashed {
+                        // Empty texts blank the line, start out with the original text instead
+                        text: orig_text.clone(),
+                        source_hash: Some(source_hash::compute(&orig_text)),
+                        orig_text: Some(orig_text)
+                    }
+                })
+                .collect::<StoryRaceTextDict>()
+        ));
         return;
     };
 
-    let text_data = get_textData(this);
     for (i, key) in unsafe { text_data.as_slice().iter().enumerate() } {
         let Some(text) = dict.get(i) else { continue };
-        Key_set_text(*key, text.to_il2cpp_string());
+        let source_ok = source_hash::check(&dict_path, text.source_hash(), || Key_get_text_string(*key), || i.to_string());
+        if source_ok {
+            Key_set_text(*key, text.text().to_il2cpp_string());
+        }
     }
 }
 
@@ -1,11 +1,10 @@
 use std::ptr::null_mut;
 
 use fnv::FnvHashMap;
-use serde::{Deserialize, Serialize};
 use widestring::Utf16Str;
 
 use crate::{
-    core::{ext::Utf16StringExt, utils, Error, Hachimi, SugoiClient}, 
+    core::{asset_dicts::{StoryTimelineDataDict, TextBlockDict}, asset_skeleton, ext::Utf16StringExt, mt, source_hash, utils, Error, Hachimi}, 
     il2cpp::{
         ext::{Il2CppStringExt, StringExt}, hook::{umamusume::{StoryTimelineCharaTrackData, StoryTimelineClipData}, UnityEngine_AssetBundleModule::AssetBundle::ASSET_PATH_PREFIX}, symbols::{get_field_from_name, get_field_object_value, get_field_value, set_field_object_value, set_field_value, IList}, types::*
     }
@@ -59,39 +58,6 @@ fn set_Length(this: *mut Il2CppObject, value: i32) {
     set_field_value(this, unsafe { LENGTH_FIELD }, &value);
 }
 
-// (Aliases are there for tlg compatibility)
-#[derive(Serialize, Deserialize, Default)]
-struct StoryTimelineDataDict {
-    #[serde(alias = "Title")]
-    title: Option<String>,
-
-    #[serde(alias = "TextBlockList")]
-    #[serde(default)]
-    text_block_list: Vec<TextBlockDict>,
-
-    #[serde(default)]
-    no_wrap: bool
-}
-
-#[derive(Serialize, Deserialize, Default)]
-struct TextBlockDict {
-    #[serde(alias = "Name")]
-    name: Option<String>,
-
-    #[serde(alias = "Text")]
-    text: Option<String>,
-
-    #[serde(alias = "ChoiceDataList")]
-    #[serde(default)]
-    choice_data_list: Vec<String>,
-
-    #[serde(alias = "ColorTextInfoList")]
-    #[serde(default)]
-    color_text_info_list: Vec<String>,
-
-    new_clip_length: Option<i32>
-}
-
 // hook::UnityEngine_AssetBundleModule::AssetBundle
 // name:
 // - assets/_gallopresources/bundle/resources/home/data/xxxxx/yy/hometimeline_xxxxx_yy_zzzzzzz.asset
@@ -147,6 +113,14 @@ pub fn on_LoadAsset(_bundle: *mut Il2CppObject, this: *mut Il2CppObject, name: &
             None
         }
     }) else {
+        asset_skeleton::save(&dict_path, || match generate_skeleton_dict(this) {
+            Ok(dict) => Some(dict),
+            Err(e) => {
+                error!("Failed to generate skeleton: {}", e);
+                None
+            }
+        });
+
         // Clip length adjustment independent of story patching
         // No need to adjust length if speed is faster
         if tcps_mult < 1.0 {
@@ -163,7 +137,13 @@ pub fn on_LoadAsset(_bundle: *mut Il2CppObject, this: *mut Il2CppObject, name: &
     );
 
     if let Some(title) = &dict.title {
-        set_Title(this, title.to_il2cpp_string());
+        let source_ok = source_hash::check(&dict_path, dict.title_source_hash.as_deref(), || {
+            let orig_title = get_Title(this);
+            if orig_title.is_null() { String::new() } else { unsafe { (*orig_title).as_utf16str().to_string() } }
+        }, || "title".to_owned());
+        if source_ok {
+            set_Title(this, title.to_il2cpp_string());
+        }
     }
 
     let Some(block_list) = IList::new(get_BlockList(this)) else {
@@ -204,6 +184,14 @@ pub fn on_LoadAsset(_bundle: *mut Il2CppObject, this: *mut Il2CppObject, name: &
             continue;
         };
 
+        let source_ok = source_hash::check(&dict_path, text_block_dict.source_hash.as_deref(), || {
+            let orig_text = StoryTimelineTextClipData::get_Text(clip_data);
+            if orig_text.is_null() { String::new() } else { unsafe { (*orig_text).as_utf16str().to_string() } }
+        }, || format!("text_block_list.{}", i));
+        if !source_ok {
+            continue;
+        }
+
         if let Some(name) = &text_block_dict.name {
             StoryTimelineTextClipData::set_Name(clip_data, name.to_il2cpp_string());
         }
@@ -391,26 +379,26 @@ fn apply_clip_length(
     new_block_len
 }
 
-fn generate_auto_tl_dict(this: *mut Il2CppObject) -> Result<StoryTimelineDataDict, Error> {
+fn get_text(text: *mut Il2CppString) -> Option<String> {
+    if !text.is_null() && unsafe { (*text).length > 0 } {
+        Some(unsafe { (*text).as_utf16str().to_string() })
+    }
+    else {
+        None
+    }
+}
+
+// Dict with only the original text and source hashes filled in (see asset_skeleton.rs)
+fn generate_skeleton_dict(this: *mut Il2CppObject) -> Result<StoryTimelineDataDict, Error> {
     let Some(block_list) = <IList>::new(get_BlockList(this)) else {
         return Err(Error::RuntimeError("Failed to get block list".to_owned()));
     };
-    let block_count = block_list.count() as usize - 1;
-
-    let mut names_tmp: Vec<String> = Vec::with_capacity(block_count);
-    let mut name_indices: FnvHashMap<String, usize> = FnvHashMap::default();
-    let mut tl_batch: Vec<String> = Vec::with_capacity(block_count);
     let mut dict = StoryTimelineDataDict::default();
 
-    // Step 1: Prepare the tl batch and prepopulate the dict with Some()
-    // so we know which ones to fill in later
-
-    let title = get_Title(this);
-    if !title.is_null() && unsafe { (*title).length > 0 } {
-        let title_str = unsafe { (*title).as_utf16str().to_string() };
+    if let Some(title_str) = get_text(get_Title(this)) {
         if title_str != "0" {
-            dict.title = Some(String::new());
-            tl_batch.push(title_str);
+            dict.title_source_hash = Some(source_hash::compute(&title_str));
+            dict.orig_title = Some(title_str);
         }
     }
 
@@ -423,37 +411,21 @@ fn generate_auto_tl_dict(this: *mut Il2CppObject) -> Result<StoryTimelineDataDic
             continue;
         };
 
-        let name = StoryTimelineTextClipData::get_Name(clip_data);
-        if !name.is_null() && unsafe { (*name).length > 0 } {
-            let name_str = unsafe { (*name).as_utf16str().to_string() };
-            if name_str != "モノローグ" && name_str != "<username>" {
-                if !name_indices.contains_key(&name_str) {
-                    name_indices.insert(name_str.clone(), names_tmp.len());
-                    names_tmp.push(name_str.clone());
-                }
-                block_dict.name = Some(name_str);
-            }
-        }
+        block_dict.orig_name = get_text(StoryTimelineTextClipData::get_Name(clip_data));
 
-        let text = StoryTimelineTextClipData::get_Text(clip_data);
-        if !text.is_null() && unsafe { (*text).length > 0 } {
-            block_dict.text = Some(String::new());
-            tl_batch.push(unsafe { (*text).as_utf16str().to_string() });
+        if let Some(text_str) = get_text(StoryTimelineTextClipData::get_Text(clip_data)) {
+            block_dict.source_hash = Some(source_hash::compute(&text_str));
+            block_dict.orig_text = Some(text_str);
         }
 
+        // always push a value for every item so they don't misalign
         let choice_data_list_obj = StoryTimelineTextClipData::get_ChoiceDataList(clip_data);
         if let Some(choice_data_list) = IList::new(choice_data_list_obj) {
             for choice_data in choice_data_list.iter() {
-                // always push a value so it doesn't misalign
                 block_dict.choice_data_list.push(String::new());
-                let text = StoryTimelineTextClipData::ChoiceData::get_Text(choice_data);
-                if !text.is_null() && unsafe { (*text).length > 0 } {
-                    tl_batch.push(unsafe { (*text).as_utf16str().to_string() });
-                }
-                else {
-                    // same here
-                    tl_batch.push(String::new());
-                }
+                block_dict.orig_choice_data_list.push(
+                    get_text(StoryTimelineTextClipData::ChoiceData::get_Text(choice_data)).unwrap_or_default()
+                );
             }
         }
 
@@ -461,43 +433,67 @@ fn generate_auto_tl_dict(this: *mut Il2CppObject) -> Result<StoryTimelineDataDic
         if let Some(color_text_info_list) = IList::new(color_text_info_list_obj) {
             for color_text_info in color_text_info_list.iter() {
                 block_dict.color_text_info_list.push(String::new());
-                let text = StoryTimelineTextClipData::ColorTextInfo::get_Text(color_text_info);
-                if !text.is_null() && unsafe { (*text).length > 0 } {
-                    tl_batch.push(unsafe { (*text).as_utf16str().to_string() });
-                }
-                else {
-                    tl_batch.push(String::new());
-                }
+                block_dict.orig_color_text_info_list.push(
+                    get_text(StoryTimelineTextClipData::ColorTextInfo::get_Text(color_text_info)).unwrap_or_default()
+                );
             }
         }
 
         dict.text_block_list.push(block_dict);
     }
 
+    Ok(dict)
+}
+
+fn generate_auto_tl_dict(this: *mut Il2CppObject) -> Result<StoryTimelineDataDict, Error> {
+    let mut dict = generate_skeleton_dict(this)?;
+
+    // Step 1: Prepare the tl batch from the original text
+    let mut names_tmp: Vec<String> = Vec::new();
+    let mut name_indices: FnvHashMap<String, usize> = FnvHashMap::default();
+    let mut tl_batch: Vec<String> = Vec::with_capacity(dict.text_block_list.len());
+
+    if let Some(title) = &dict.orig_title {
+        tl_batch.push(title.clone());
+    }
+
+    for block_dict in dict.text_block_list.iter() {
+        if let Some(name) = &block_dict.orig_name {
+            if name != "モノローグ" && name != "<username>" && !name_indices.contains_key(name) {
+                name_indices.insert(name.clone(), names_tmp.len());
+                names_tmp.push(name.clone());
+            }
+        }
+
+        if let Some(text) = &block_dict.orig_text {
+            tl_batch.push(text.clone());
+        }
+
+        tl_batch.extend(block_dict.orig_choice_data_list.iter().cloned());
+        tl_batch.extend(block_dict.orig_color_text_info_list.iter().cloned());
+    }
+
     // push name entries to the end of the batch
     let names_count = names_tmp.len();
     tl_batch.append(&mut names_tmp);
 
     // Step 2: Send it to the tl server
-    let mut translated = SugoiClient::instance().translate(&tl_batch)?;
-    if translated.len() != tl_batch.len() {
-        return Err(Error::RuntimeError("Server returned invalid amount of translated content".to_owned()));
-    }
+    let mut translated = mt::translate(&tl_batch)?;
     // split off names section
     let translated_names = translated.split_off(translated.len() - names_count);
 
     // Step 3: Fill in dict with translated content
     let mut tl_iter = translated.into_iter();
-    if dict.title.is_some() {
+    if dict.orig_title.is_some() {
         dict.title = tl_iter.next();
     }
 
     for block_dict in dict.text_block_list.iter_mut() {
-        if let Some(name) = block_dict.name.as_ref() {
-            block_dict.name = Some(translated_names[name_indices[name]].clone());
+        if let Some(i) = block_dict.orig_name.as_ref().and_then(|name| name_indices.get(name)) {
+            block_dict.name = Some(translated_names[*i].clone());
         }
 
-        if block_dict.text.is_some() {
+        if block_dict.orig_text.is_some() {
             block_dict.text = tl_iter.next();
         }
 
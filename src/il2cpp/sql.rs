@@ -1,10 +1,11 @@
 use std::sync::atomic::{self, AtomicBool};
 
+use fnv::FnvHashMap;
 use sqlparser::ast;
 
 use crate::{
-    core::{utils, Hachimi},
-    il2cpp::{ext::StringExt, hook::LibNative_Runtime, types::{Il2CppObject, Il2CppString}}
+    core::{localized_data::SqlTableConfig, mt::master_text, untranslated, utils, Hachimi},
+    il2cpp::{ext::{Il2CppStringExt, StringExt}, hook::LibNative_Runtime, types::{Il2CppObject, Il2CppString}}
 };
 
 // All of this add column/param stuff could be simplified to two hash maps, but that's overkill.
@@ -14,22 +15,46 @@ pub trait SelectQueryState {
     /// Implementers are expected to only track the index of columns that they need.
     fn add_column(&mut self, idx: i32, name: &str);
 
-    /// Adds a placeholder parameter to the query (WHERE param = ?).
+    /// Gets a column that's used in the WHERE clause (WHERE param = ?, WHERE param = 123).
     /// 
-    /// Index starts at 1.
-    fn add_param(&mut self, idx: i32, name: &str);
+    /// Returns None if the column isn't needed.
+    fn get_param_column(&mut self, name: &str) -> Option<&mut Column>;
 
-    /// Bind an int value to a placeholder.
+    /// Bind a value to a placeholder.
     /// 
     /// Index starts at 1.
-    fn bind_int(&mut self, idx: i32, value: i32);
+    fn bind(&mut self, idx: i32, value: &Value);
 
     /// Gets the resulting string on the current row's column.
     fn get_text(&self, query: *mut Il2CppObject, idx: i32) -> Option<*mut Il2CppString>;
 }
 
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Value {
+    Int(i32),
+    Text(String)
+}
+
+impl Value {
+    fn as_int(&self) -> Option<i32> {
+        match self {
+            Value::Int(v) => Some(*v),
+            Value::Text(s) => s.parse().ok()
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(v) => write!(f, "{}", v),
+            Value::Text(s) => f.write_str(s)
+        }
+    }
+}
+
 #[derive(Default)]
-struct Column {
+pub struct Column {
     /// Index of the column in the SELECT statement.
     /// 
     /// Can be used to query the value later if needed.
@@ -40,8 +65,13 @@ struct Column {
     /// If this column's value is already binded as a param in the query, we won't need to query it later.
     param_idx: Option<i32>,
 
-    /// The int value binded to this column as a parameter.
-    int_value: Option<i32>
+    /// The value binded to this column as a parameter, or the literal that it's compared against.
+    value: Option<Value>,
+
+    /// The column is compared against more than one value (IN, OR), so the bound value can't be trusted.
+    /// 
+    /// The value has to be queried from the row instead.
+    ambiguous: bool
 }
 
 impl Column {
@@ -63,10 +93,40 @@ impl Column {
         }
     }
 
-    fn try_bind_int(&mut self, idx: i32, value: i32) {
+    fn has_constraint(&self) -> bool {
+        self.param_idx.is_some() || self.value.is_some()
+    }
+
+    /// `exact` is false if the row might not have this value (e.g. the comparison is inside of an OR).
+    fn add_param(&mut self, idx: i32, exact: bool) {
+        if !exact || self.has_constraint() {
+            self.ambiguous = true;
+        }
+        else {
+            self.param_idx = Some(idx);
+        }
+    }
+
+    fn add_literal(&mut self, value: &Value, exact: bool) {
+        if !exact || self.has_constraint() {
+            self.ambiguous = true;
+        }
+        else {
+            self.value = Some(value.clone());
+        }
+    }
+
+    fn try_bind(&mut self, idx: i32, value: &Value) {
         if self.is_param_idx(idx) {
-            self.int_value = Some(value);
+            self.value = Some(value.clone());
+        }
+    }
+
+    fn int_value(&self) -> Option<i32> {
+        if self.ambiguous {
+            return None;
         }
+        self.value.as_ref()?.as_int()
     }
 
     fn try_get_int(&self, query: *mut Il2CppObject) -> Option<i32> {
@@ -79,7 +139,7 @@ impl Column {
     }
 
     fn value_or_try_get_int(&self, query: *mut Il2CppObject) -> Option<i32> {
-        if let Some(value) = self.int_value {
+        if let Some(value) = self.int_value() {
             Some(value)
         }
         else if let Some(value) = self.try_get_int(query) {
@@ -89,6 +149,27 @@ impl Column {
             None
         }
     }
+
+    // Works for both int and text columns since sqlite converts the value
+    fn value_or_try_get_string(&self, query: *mut Il2CppObject) -> Option<String> {
+        if let Some(value) = self.value.as_ref().filter(|_| !self.ambiguous) {
+            Some(value.to_string())
+        }
+        else if let Some(idx) = self.select_idx {
+            Some(get_orig_text(query, idx))
+        }
+        else {
+            None
+        }
+    }
+}
+
+fn get_orig_text(query: *mut Il2CppObject, idx: i32) -> String {
+    let text = LibNative_Runtime::Sqlite3::Query::GetText_orig(query, idx);
+    if text.is_null() {
+        return String::new();
+    }
+    unsafe { (*text).as_utf16str().to_string() }
 }
 
 // text_data
@@ -123,103 +204,95 @@ impl TextDataQuery {
         TDQ_IS_SKILL_LEARNING_QUERY.load(atomic::Ordering::Relaxed)
     }
 
-    fn get_skill_name(index: i32) -> Option<*mut Il2CppString> {
-        // Return None if skill name translation is disabled
-        if Hachimi::instance().config.load().disable_skill_name_translation {
-            return None;
-        }
-
-        let localized_data = Hachimi::instance().localized_data.load();
-        let text_opt = localized_data
-            .text_data_dict
-            .get(&47)
-            .map(|c| c.get(&index))
-            .unwrap_or_default();
-
-        if let Some(text) = text_opt {
-            // Fit the text when it's being used in the skill learning screen
-            if Self::is_skill_learning_query() {
-                if let Some(fitted) = utils::fit_text(text, Self::SKILL_NAME_LINE_WIDTH, Self::SKILL_NAME_FONT_SIZE) {
-                    return Some(fitted.to_il2cpp_string())
-                }
-            }
-            Some(text.to_il2cpp_string())
+    fn get_dict_index(category: i32, index: i32) -> i32 {
+        // Inherited skills use a different id for some reason
+        if category == 48 && index > 900000 && index < 1000000 {
+            index - 800000
         }
         else {
-            None
+            index
         }
     }
 
-    fn get_skill_desc(mut index: i32) -> Option<*mut Il2CppString> {
-        // Inherited skills use a different id for some reason
-        if index > 900000 && index < 1000000 {
-            index -= 800000
+    fn get_skill_name(text: &str) -> *mut Il2CppString {
+        // Fit the text when it's being used in the skill learning screen
+        if Self::is_skill_learning_query() {
+            if let Some(fitted) = utils::fit_text(text, Self::SKILL_NAME_LINE_WIDTH, Self::SKILL_NAME_FONT_SIZE) {
+                return fitted.to_il2cpp_string();
+            }
         }
+        text.to_il2cpp_string()
+    }
 
-        let localized_data = Hachimi::instance().localized_data.load();
-        let text_opt = localized_data
-            .text_data_dict
-            .get(&48)
-            .map(|c| c.get(&index))
-            .unwrap_or_default();
-
-        if let Some(text) = text_opt {
-            // Do some prewrapping when it's being used in the skill learning screen
-            if Self::is_skill_learning_query() {
-                if let Some(fitted) = utils::wrap_fit_text(text,
-                    Self::SKILL_DESC_LINE_WIDTH, Self::SKILL_DESC_LINE_COUNT, Self::SKILL_DESC_FONT_SIZE
-                ) {
-                    return Some(fitted.to_il2cpp_string());
-                }
+    fn get_skill_desc(text: &str) -> *mut Il2CppString {
+        // Do some prewrapping when it's being used in the skill learning screen
+        if Self::is_skill_learning_query() {
+            if let Some(fitted) = utils::wrap_fit_text(text,
+                Self::SKILL_DESC_LINE_WIDTH, Self::SKILL_DESC_LINE_COUNT, Self::SKILL_DESC_FONT_SIZE
+            ) {
+                return fitted.to_il2cpp_string();
             }
-            Some(text.to_il2cpp_string())
-        }
-        else {
-            None
         }
+        text.to_il2cpp_string()
     }
 }
 
 impl SelectQueryState for TextDataQuery {
     fn add_column(&mut self, idx: i32, name: &str) {
-        if name == "text" {
-            self.text.select_idx = Some(idx)
+        match name {
+            "text" => self.text.select_idx = Some(idx),
+            // only needed when they're not bound to a single value (IN, OR)
+            "category" => self.category.select_idx = Some(idx),
+            "index" => self.index.select_idx = Some(idx),
+            _ => ()
         }
     }
 
-    fn add_param(&mut self, idx: i32, name: &str) {
+    fn get_param_column(&mut self, name: &str) -> Option<&mut Column> {
         match name {
-            "category" => self.category.param_idx = Some(idx),
-            "index" => self.index.param_idx = Some(idx),
-            _ => ()
+            "category" => Some(&mut self.category),
+            "index" => Some(&mut self.index),
+            _ => None
         }
     }
 
-    fn bind_int(&mut self, idx: i32, value: i32) {
-        self.category.try_bind_int(idx, value);
-        self.index.try_bind_int(idx, value);
+    fn bind(&mut self, idx: i32, value: &Value) {
+        self.category.try_bind(idx, value);
+        self.index.try_bind(idx, value);
     }
 
-    fn get_text(&self, _query: *mut Il2CppObject, idx: i32) -> Option<*mut Il2CppString> {
+    fn get_text(&self, query: *mut Il2CppObject, idx: i32) -> Option<*mut Il2CppString> {
         if !self.text.is_select_idx(idx) {
             return None;
         }
 
-        if let Some(category) = self.category.int_value {
-            if let Some(index) = self.index.int_value {
-                // specialized handlers
-                match category {
-                    47 => return Self::get_skill_name(index),
-                    48 => return Self::get_skill_desc(index),
-                    _ => ()
+        if let Some(category) = self.category.value_or_try_get_int(query) {
+            if let Some(index) = self.index.value_or_try_get_int(query) {
+                // Skill name translation can be disabled
+                if category == 47 && Hachimi::instance().config.load().disable_skill_name_translation {
+                    return None;
+                }
+
+                let index = Self::get_dict_index(category, index);
+                let localized_data = Hachimi::instance().localized_data.load();
+                let mt_text;
+                let text = match localized_data.text_data_dict.get(&category).and_then(|c| c.get(&index)) {
+                    Some(text) => text,
+                    None => {
+                        untranslated::record(untranslated::Source::TextData { category, index }, || get_orig_text(query, idx));
+                        mt_text = master_text::translate(
+                            master_text::Row::TextData { category, index }, || get_orig_text(query, idx)
+                        )?;
+                        &mt_text
+                    }
                 };
 
-                
-                return Hachimi::instance().localized_data.load()
-                    .text_data_dict
-                    .get(&category)
-                    .map(|c| c.get(&index).map(|s| s.to_il2cpp_string()))
-                    .unwrap_or_default()
+                // specialized handlers
+                return Some(match category {
+                    47 => Self::get_skill_name(text),
+                    48 => Self::get_skill_desc(text),
+                    _ => text.to_il2cpp_string()
+                });
             }
         }
 
@@ -244,22 +317,23 @@ impl SelectQueryState for CharacterSystemTextQuery {
     fn add_column(&mut self, idx: i32, name: &str) {
         match name {
             "text" => self.text.select_idx = Some(idx),
+            "character_id" => self.character_id.select_idx = Some(idx),
             "voice_id" => self.voice_id.select_idx = Some(idx),
             _ => ()
         }
     }
 
-    fn add_param(&mut self, idx: i32, name: &str) {
+    fn get_param_column(&mut self, name: &str) -> Option<&mut Column> {
         match name {
-            "character_id" => self.character_id.param_idx = Some(idx),
-            "voice_id" => self.voice_id.param_idx = Some(idx),
-            _ => ()
+            "character_id" => Some(&mut self.character_id),
+            "voice_id" => Some(&mut self.voice_id),
+            _ => None
         }
     }
 
-    fn bind_int(&mut self, idx: i32, value: i32) {
-        self.character_id.try_bind_int(idx, value);
-        self.voice_id.try_bind_int(idx, value);
+    fn bind(&mut self, idx: i32, value: &Value) {
+        self.character_id.try_bind(idx, value);
+        self.voice_id.try_bind(idx, value);
     }
 
     fn get_text(&self, query: *mut Il2CppObject, idx: i32) -> Option<*mut Il2CppString> {
@@ -267,13 +341,24 @@ impl SelectQueryState for CharacterSystemTextQuery {
             return None;
         }
 
-        if let Some(character_id) = self.character_id.int_value {
+        if let Some(character_id) = self.character_id.value_or_try_get_int(query) {
             if let Some(voice_id) = self.voice_id.value_or_try_get_int(query) {
-                return Hachimi::instance().localized_data.load()
+                let text = Hachimi::instance().localized_data.load()
                     .character_system_text_dict
                     .get(&character_id)
                     .map(|c| c.get(&voice_id).map(|s| s.to_il2cpp_string()))
-                    .unwrap_or_default()
+                    .unwrap_or_default();
+
+                if text.is_none() {
+                    untranslated::record(
+                        untranslated::Source::CharacterSystemText { character_id, voice_id },
+                        || get_orig_text(query, idx)
+                    );
+                    return master_text::translate(
+                        master_text::Row::CharacterSystemText { character_id, voice_id }, || get_orig_text(query, idx)
+                    ).map(|s| s.to_il2cpp_string());
+                }
+                return text;
             }
         }
 
@@ -298,9 +383,11 @@ impl SelectQueryState for RaceJikkyoCommentQuery {
         }
     }
 
-    fn add_param(&mut self, _idx: i32, _name: &str) {}
+    fn get_param_column(&mut self, _name: &str) -> Option<&mut Column> {
+        None
+    }
 
-    fn bind_int(&mut self, _idx: i32, _value: i32) {}
+    fn bind(&mut self, _idx: i32, _value: &Value) {}
 
     fn get_text(&self, query: *mut Il2CppObject, idx: i32) -> Option<*mut Il2CppString> {
         if !self.message.is_select_idx(idx) {
@@ -312,6 +399,9 @@ impl SelectQueryState for RaceJikkyoCommentQuery {
                 .race_jikkyo_comment_dict
                 .get(&id)
                 .map(|s| s.to_il2cpp_string())
+                .or_else(|| master_text::translate(
+                    master_text::Row::RaceJikkyoComment { id }, || get_orig_text(query, idx)
+                ).map(|s| s.to_il2cpp_string()));
         }
 
         None
@@ -335,9 +425,11 @@ impl SelectQueryState for RaceJikkyoMessageQuery {
         }
     }
 
-    fn add_param(&mut self, _idx: i32, _name: &str) {}
+    fn get_param_column(&mut self, _name: &str) -> Option<&mut Column> {
+        None
+    }
 
-    fn bind_int(&mut self, _idx: i32, _value: i32) {}
+    fn bind(&mut self, _idx: i32, _value: &Value) {}
 
     fn get_text(&self, query: *mut Il2CppObject, idx: i32) -> Option<*mut Il2CppString> {
         if !self.message.is_select_idx(idx) {
@@ -349,12 +441,96 @@ impl SelectQueryState for RaceJikkyoMessageQuery {
                 .race_jikkyo_message_dict
                 .get(&id)
                 .map(|s| s.to_il2cpp_string())
+                .or_else(|| master_text::translate(
+                    master_text::Row::RaceJikkyoMessage { id }, || get_orig_text(query, idx)
+                ).map(|s| s.to_il2cpp_string()));
         }
 
         None
     }
 }
 
+// Tables from the localized data config (sql_tables)
+pub struct ConfigTableQuery {
+    table: String,
+    key_names: Vec<String>,
+    text_name: String,
+
+    // SELECT
+    text: Column,
+
+    // SELECT or WHERE
+    keys: Vec<Column>
+}
+
+impl ConfigTableQuery {
+    pub fn new(config: &SqlTableConfig) -> ConfigTableQuery {
+        ConfigTableQuery {
+            table: config.table.clone(),
+            key_names: config.key_columns.clone(),
+            text_name: config.text_column.clone(),
+            text: Column::default(),
+            keys: config.key_columns.iter().map(|_| Column::default()).collect()
+        }
+    }
+
+    fn get_key(&self, query: *mut Il2CppObject) -> Option<String> {
+        let mut key = String::new();
+        for (i, column) in self.keys.iter().enumerate() {
+            if i != 0 {
+                key.push(',');
+            }
+            key += &column.value_or_try_get_string(query)?;
+        }
+        Some(key)
+    }
+
+}
+
+impl SelectQueryState for ConfigTableQuery {
+    fn add_column(&mut self, idx: i32, name: &str) {
+        if name == self.text_name {
+            self.text.select_idx = Some(idx);
+        }
+
+        if let Some(i) = self.key_names.iter().position(|n| n == name) {
+            self.keys[i].select_idx = Some(idx);
+        }
+    }
+
+    fn get_param_column(&mut self, name: &str) -> Option<&mut Column> {
+        let i = self.key_names.iter().position(|n| n == name)?;
+        Some(&mut self.keys[i])
+    }
+
+    fn bind(&mut self, idx: i32, value: &Value) {
+        for column in self.keys.iter_mut() {
+            column.try_bind(idx, value);
+        }
+    }
+
+    fn get_text(&self, query: *mut Il2CppObject, idx: i32) -> Option<*mut Il2CppString> {
+        if !self.text.is_select_idx(idx) {
+            return None;
+        }
+
+        let key = self.get_key(query)?;
+        let text = Hachimi::instance().localized_data.load()
+            .sql_table_dicts
+            .get(&self.table)
+            .map(|d| d.get(&key).map(|s| s.to_il2cpp_string()))
+            .unwrap_or_default();
+
+        if text.is_none() && untranslated::is_enabled() {
+            untranslated::record(
+                untranslated::Source::SqlTable { table: self.table.clone(), key },
+                || get_orig_text(query, idx)
+            );
+        }
+        text
+    }
+}
+
 
 // sqlparser extensions
 pub trait SelectExt {
@@ -390,62 +566,287 @@ impl SelectItemExt for ast::SelectItem {
 }
 
 pub trait ExprExt {
-    fn binary_op_iter<'a>(&'a self) -> BinaryOpIter<'a>;
     fn get_ident_value(&self) -> Option<&String>;
-    fn is_placeholder_value(&self) -> bool;
+    fn get_placeholder(&self) -> Option<&String>;
+    fn get_literal_value(&self) -> Option<Value>;
 }
 
 impl ExprExt for ast::Expr {
-    fn binary_op_iter<'a>(&'a self) -> BinaryOpIter<'a> {
-        BinaryOpIter { stack: vec![self] }
+    fn get_ident_value(&self) -> Option<&String> {
+        match self {
+            ast::Expr::Identifier(_) if self.get_placeholder().is_some() => None,
+            ast::Expr::Identifier(ident) => Some(&ident.value),
+            // table.column
+            ast::Expr::CompoundIdentifier(idents) => idents.last().map(|ident| &ident.value),
+            _ => None
+        }
     }
 
-    fn get_ident_value(&self) -> Option<&String> {
-        if let ast::Expr::Identifier(ident) = self {
-            return Some(&ident.value);
+    fn get_placeholder(&self) -> Option<&String> {
+        match self {
+            ast::Expr::Value(ast::Value::Placeholder(s)) => Some(s),
+            // The SQLite dialect parses $name params as identifiers
+            ast::Expr::Identifier(ident) if ident.quote_style.is_none() && ident.value.starts_with('$') => Some(&ident.value),
+            _ => None
         }
+    }
 
-        None
+    fn get_literal_value(&self) -> Option<Value> {
+        match self {
+            ast::Expr::Value(ast::Value::Number(s, _)) => Some(s.parse().map(Value::Int).unwrap_or_else(|_| Value::Text(s.clone()))),
+            ast::Expr::Value(ast::Value::SingleQuotedString(s)) => Some(Value::Text(s.clone())),
+            ast::Expr::UnaryOp { op: ast::UnaryOperator::Minus, expr } => match expr.get_literal_value()? {
+                Value::Int(v) => Some(Value::Int(-v)),
+                Value::Text(s) => Some(Value::Text(format!("-{}", s)))
+            },
+            _ => None
+        }
+    }
+}
+
+/// Tracks the placeholders in a WHERE clause and feeds the column constraints to the query state.
+/// 
+/// Supported predicates: `column = ?`, `column = <literal>` and `column IN (...)`, joined by AND/OR.
+/// Placeholders in anything else are still counted so that the indices stay correct.
+pub struct SelectionVisitor<'a> {
+    state: &'a mut dyn SelectQueryState,
+    // Index of the next anonymous placeholder
+    next_param_idx: i32,
+    // Named placeholders (:name, @name, $name) share the same index each time they appear
+    named_params: FnvHashMap<String, i32>,
+    // Depth of OR expressions that we're in, comparisons inside of them aren't guaranteed to match the row
+    or_depth: u32
+}
+
+impl<'a> SelectionVisitor<'a> {
+    pub fn new(state: &'a mut dyn SelectQueryState) -> SelectionVisitor<'a> {
+        SelectionVisitor { state, next_param_idx: 1, named_params: FnvHashMap::default(), or_depth: 0 }
+    }
+
+    /// Returns the index of the placeholder, following sqlite's rules for ?NNN and named params.
+    fn param_idx(&mut self, placeholder: &str) -> i32 {
+        if let Some(&idx) = self.named_params.get(placeholder) {
+            return idx;
+        }
+
+        let idx = match placeholder.strip_prefix('?') {
+            Some(num) => num.parse::<i32>().unwrap_or(self.next_param_idx),
+            None => {
+                self.named_params.insert(placeholder.to_owned(), self.next_param_idx);
+                self.next_param_idx
+            }
+        };
+        self.next_param_idx = self.next_param_idx.max(idx + 1);
+        idx
     }
 
-    fn is_placeholder_value(&self) -> bool {
-        if let ast::Expr::Value(value) = self {
-            if let ast::Value::Placeholder(_) = value {
-                return true;
+    // Compares the column against the value expression
+    fn add_constraint(&mut self, name: &str, value: &ast::Expr) {
+        let exact = self.or_depth == 0;
+        if let Some(placeholder) = value.get_placeholder() {
+            let idx = self.param_idx(placeholder);
+            if let Some(column) = self.state.get_param_column(name) {
+                column.add_param(idx, exact);
+            }
+        }
+        else if let Some(value) = value.get_literal_value() {
+            if let Some(column) = self.state.get_param_column(name) {
+                column.add_literal(&value, exact);
             }
         }
+        else {
+            self.visit_other(value);
+        }
+    }
+
+    pub fn visit(&mut self, expr: &ast::Expr) {
+        match expr {
+            ast::Expr::BinaryOp { left, op: ast::BinaryOperator::Eq, right } => {
+                if let Some(name) = left.get_ident_value() {
+                    self.add_constraint(name, right);
+                }
+                // ? = column
+                else if let Some(name) = right.get_ident_value() {
+                    self.add_constraint(name, left);
+                }
+                else {
+                    self.visit_other(expr);
+                }
+            }
 
-        false
+            ast::Expr::BinaryOp { left, op: ast::BinaryOperator::And, right } => {
+                self.visit(left);
+                self.visit(right);
+            }
+
+            ast::Expr::BinaryOp { left, op: ast::BinaryOperator::Or, right } => {
+                self.or_depth += 1;
+                self.visit(left);
+                self.visit(right);
+                self.or_depth -= 1;
+            }
+
+            ast::Expr::InList { expr, list, negated: false } => {
+                let Some(name) = expr.get_ident_value() else {
+                    self.visit_other(expr);
+                    list.iter().for_each(|e| self.visit_other(e));
+                    return;
+                };
+
+                // Same as column = a OR column = b...
+                if list.len() > 1 {
+                    self.or_depth += 1;
+                }
+                for value in list.iter() {
+                    self.add_constraint(name, value);
+                }
+                if list.len() > 1 {
+                    self.or_depth -= 1;
+                }
+            }
+
+            ast::Expr::Nested(expr) => self.visit(expr),
+
+            _ => self.visit_other(expr)
+        }
     }
-}
 
-pub struct BinaryOpIter<'a> {
-    stack: Vec<&'a ast::Expr>
-}
+    // Only counts the placeholders
+    fn visit_other(&mut self, expr: &ast::Expr) {
+        if let Some(placeholder) = expr.get_placeholder() {
+            self.param_idx(placeholder);
+            return;
+        }
 
-pub struct BinaryOpRef<'a> {
-    pub left: &'a Box<ast::Expr>,
-    pub op: &'a ast::BinaryOperator,
-    pub right: &'a Box<ast::Expr>
+        match expr {
+            ast::Expr::BinaryOp { left, right, .. } => {
+                self.visit_other(left);
+                self.visit_other(right);
+            }
+            ast::Expr::UnaryOp { expr, .. } |
+            ast::Expr::Nested(expr) |
+            ast::Expr::IsNull(expr) |
+            ast::Expr::IsNotNull(expr) |
+            ast::Expr::Cast { expr, .. } => self.visit_other(expr),
+            ast::Expr::InList { expr, list, .. } => {
+                self.visit_other(expr);
+                list.iter().for_each(|e| self.visit_other(e));
+            }
+            ast::Expr::Between { expr, low, high, .. } => {
+                self.visit_other(expr);
+                self.visit_other(low);
+                self.visit_other(high);
+            }
+            ast::Expr::Like { expr, pattern, .. } => {
+                self.visit_other(expr);
+                self.visit_other(pattern);
+            }
+            _ => ()
+        }
+    }
 }
 
-impl<'a> Iterator for BinaryOpIter<'a> {
-    type Item = BinaryOpRef<'a>;
+#[cfg(test)]
+mod tests {
+    use sqlparser::{dialect::SQLiteDialect, keywords::Keyword, parser::Parser};
+
+    use super::*;
+
+    // Same steps as Connection::parse_query
+    fn parse(sql: &str, key_columns: &[&str]) -> ConfigTableQuery {
+        let mut state = ConfigTableQuery::new(&SqlTableConfig {
+            table: "test".to_owned(),
+            key_columns: key_columns.iter().map(|s| s.to_string()).collect(),
+            text_column: "text".to_owned(),
+            dict: String::new()
+        });
+
+        let dialect = SQLiteDialect {};
+        let mut parser = Parser::new(&dialect).try_with_sql(sql).unwrap();
+        assert!(parser.parse_keyword(Keyword::SELECT));
+        let select = parser.parse_select().unwrap();
+        let mut i = 0;
+        for item in select.projection.iter() {
+            if let Some(name) = item.get_unnamed_expr_ident() {
+                state.add_column(i, name);
+                i += 1;
+            }
+        }
+        if let Some(selection) = &select.selection {
+            SelectionVisitor::new(&mut state).visit(selection);
+        }
+        state
+    }
 
-    fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let Some(expr) = self.stack.pop() else {
-                return None;
-            };
+    fn param_idxs(state: &ConfigTableQuery) -> Vec<Option<i32>> {
+        state.keys.iter().map(|c| c.param_idx).collect()
+    }
 
-            let ast::Expr::BinaryOp { left, op, right } = expr else {
-                continue;
-            };
+    #[test]
+    fn eq_params() {
+        let state = parse("SELECT text FROM test WHERE a = ? AND ? = b", &["a", "b"]);
+        assert_eq!(param_idxs(&state), [Some(1), Some(2)]);
+        assert!(state.keys.iter().all(|c| !c.ambiguous));
+        assert_eq!(state.text.select_idx, Some(0));
+    }
 
-            self.stack.push(right);
-            self.stack.push(left); // left will be pop'd first
+    #[test]
+    fn literals() {
+        let mut state = parse("SELECT text FROM test WHERE a = 12 AND b = 'x' AND c = -3", &["a", "b", "c"]);
+        assert_eq!(state.keys[0].int_value(), Some(12));
+        assert_eq!(state.keys[1].value, Some(Value::Text("x".to_owned())));
+        assert_eq!(state.keys[2].int_value(), Some(-3));
 
-            return Some(BinaryOpRef { left, op, right })
-        }
+        // Literals aren't affected by binds
+        state.bind(1, &Value::Int(5));
+        assert_eq!(state.keys[0].int_value(), Some(12));
+    }
+
+    #[test]
+    fn in_list() {
+        let state = parse("SELECT text, a FROM test WHERE a IN (?, ?) AND b IN (?)", &["a", "b"]);
+        assert!(state.keys[0].ambiguous);
+        assert_eq!(state.keys[0].select_idx, Some(1));
+        assert!(!state.keys[1].ambiguous);
+        assert_eq!(state.keys[1].param_idx, Some(3));
+    }
+
+    #[test]
+    fn or_is_inexact() {
+        let state = parse("SELECT text FROM test WHERE (a = ? OR a = 2) AND b = ?", &["a", "b"]);
+        assert!(state.keys[0].ambiguous);
+        assert_eq!(state.keys[0].int_value(), None);
+        assert!(!state.keys[1].ambiguous);
+        assert_eq!(state.keys[1].param_idx, Some(2));
+    }
+
+    #[test]
+    fn numbered_params() {
+        let state = parse("SELECT text FROM test WHERE a = ?2 AND b = ?1", &["a", "b"]);
+        assert_eq!(param_idxs(&state), [Some(2), Some(1)]);
+    }
+
+    #[test]
+    fn mixed_params() {
+        // Anonymous params continue after the largest index so far
+        let state = parse("SELECT text FROM test WHERE a = ?5 AND b = ? AND c = ?2 AND d = ?", &["a", "b", "c", "d"]);
+        assert_eq!(param_idxs(&state), [Some(5), Some(6), Some(2), Some(7)]);
+
+        // Placeholders outside of the supported predicates are still counted
+        let state = parse("SELECT text FROM test WHERE x > ? AND a = ?", &["a"]);
+        assert_eq!(param_idxs(&state), [Some(2)]);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn named_params() {
+        let mut state = parse("SELECT text FROM test WHERE a = :id AND b = ? AND c = :id AND d = :other", &["a", "b", "c", "d"]);
+        assert_eq!(param_idxs(&state), [Some(1), Some(2), Some(1), Some(3)]);
+
+        state.bind(1, &Value::Int(42));
+        assert_eq!(state.keys[0].int_value(), Some(42));
+        assert_eq!(state.keys[2].int_value(), Some(42));
+
+        let state = parse("SELECT text FROM test WHERE a = @id AND b = $id AND c = @id", &["a", "b", "c"]);
+        assert_eq!(param_idxs(&state), [Some(1), Some(2), Some(1)]);
+    }
+}
@@ -70,7 +70,10 @@ pub struct Config {
     #[serde(default)]
     pub block_minimize_in_full_screen: bool,
     #[serde(default)]
-    pub window_always_on_top: bool
+    pub window_always_on_top: bool,
+    // Tried in order if the GitHub API can't be reached, must return the same data as its latest release endpoint
+    #[serde(default)]
+    pub update_mirrors: Vec<String>
 }
 
 impl Config {
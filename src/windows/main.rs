@@ -3,7 +3,7 @@ use std::os::raw::{c_ulong, c_void};
 use widestring::U16CString;
 use windows::{core::PCWSTR, Win32::{Foundation::{BOOL, HMODULE, TRUE}, System::LibraryLoader::LoadLibraryW}};
 
-use crate::{core::{plugin_api::Plugin, Hachimi}, windows::utils};
+use crate::{core::{mt, plugin_api::Plugin, Hachimi}, windows::utils};
 
 use super::{hook, wnd_hook};
 
@@ -63,6 +63,7 @@ pub extern "C" fn DllMain(hmodule: HMODULE, call_reason: c_ulong, _reserved: *mu
 
         info!("Unhooking everything");
         Hachimi::instance().interceptor.unhook_all();
+        mt::cache::flush_or_log();
     }
     TRUE
 }
\ No newline at end of file
@@ -44,7 +44,9 @@ impl Updater {
             mutex.lock().unwrap().show_notification(&t!("notification.checking_for_updates"));
         }
 
-        let latest: Release = http::get_json(&format!("https://api.github.com/repos/{}/releases/latest", REPO_PATH))?;
+        let mut urls = vec![format!("https://api.github.com/repos/{}/releases/latest", REPO_PATH)];
+        urls.extend(Hachimi::instance().config.load().windows.update_mirrors.iter().cloned());
+        let latest: Release = http::with_failover(&urls, http::get_json)?;
         if latest.is_different_version() {
             let mut installer_asset = None;
             for asset in latest.assets {
@@ -107,7 +109,7 @@ impl Updater {
         // Download the installer
         let installer_path = utils::get_tmp_installer_path();
 
-        let res = ureq::get(&asset.browser_download_url).call()?;
+        let res = http::agent().get(&asset.browser_download_url).call()?;
         std::io::copy(&mut res.into_reader(), &mut File::create(&installer_path)?)?;
 
         // Launch the installer
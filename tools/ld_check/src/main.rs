@@ -0,0 +1,428 @@
+/*
+    Localized data checker
+    Loads a localized data dir through Hachimi's own loading code and reports anything that
+    would fail in-game (unparsable dicts/asset dicts, bad plural forms, broken templates...)
+
+    Usage: ld_check <localized_data_dir> [overlay_dir...]
+    Overlays are given in the same order as `localized_data_overlay_dirs`.
+    Exits with a non-zero code if any error was found.
+*/
+#[macro_use] extern crate log;
+
+use std::{fs, path::{Path, PathBuf}, process, sync::{atomic::{self, AtomicUsize}, Arc}};
+
+use arc_swap::ArcSwap;
+use serde::de::{DeserializeOwned, IgnoredAny};
+
+use crate::core::{
+    asset_dicts::{AnRootData, FlashActionPlayerData, LyricsDict, StoryRaceTextDict, StoryTimelineDataDict},
+    dict_cache,
+    localized_data::{AssetInfo, LocalizedData, LocalizedDataConfig},
+    pattern_dict, plurals, template, template_filters, Hachimi
+};
+
+#[path = "../../../src/core"]
+#[allow(dead_code)]
+mod core {
+    mod error;
+    pub use error::Error;
+
+    pub mod asset_dicts;
+    pub mod dict_cache;
+    pub mod glossary;
+    pub mod localized_data;
+    pub mod pattern_dict;
+    pub mod plurals;
+    pub mod po;
+    pub mod template;
+    pub mod template_filters;
+
+    use std::sync::Arc;
+    use arc_swap::ArcSwap;
+    use once_cell::sync::OnceCell;
+
+    // Stand-in for the real instance, the template filters only need the localized data
+    pub struct Hachimi {
+        pub localized_data: ArcSwap<localized_data::LocalizedData>
+    }
+
+    pub static INSTANCE: OnceCell<Arc<Hachimi>> = OnceCell::new();
+
+    impl Hachimi {
+        pub fn instance() -> Arc<Hachimi> {
+            INSTANCE.get().expect("instance").clone()
+        }
+    }
+}
+
+static ERROR_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if record.level() == log::Level::Error {
+            ERROR_COUNT.fetch_add(1, atomic::Ordering::Relaxed);
+        }
+        eprintln!("[{}] {}", record.level(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+fn report(file: &str, key: &str, msg: impl std::fmt::Display) {
+    if key.is_empty() {
+        error!("{}: {}", file, msg);
+    }
+    else {
+        error!("{}: {}: {}", file, key, msg);
+    }
+}
+
+fn main() {
+    log::set_logger(&Logger).expect("logger");
+    log::set_max_level(log::LevelFilter::Warn);
+    // Don't leave cache files in the checked dirs
+    dict_cache::set_enabled(false);
+
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some(ld_dir) = args.first() else {
+        eprintln!("Usage: ld_check <localized_data_dir> [overlay_dir...]");
+        process::exit(2);
+    };
+    let ld_path = PathBuf::from(ld_dir);
+    let overlay_paths: Vec<PathBuf> = args[1..].iter().rev().map(PathBuf::from).collect();
+
+    check_config(&ld_path);
+
+    let localized_data = match LocalizedData::load(Some(ld_path.clone()), overlay_paths.clone()) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Failed to load localized data: {}", e);
+            finish();
+        }
+    };
+    _ = core::INSTANCE.set(Arc::new(Hachimi {
+        localized_data: ArcSwap::new(Arc::new(localized_data))
+    }));
+
+    let localized_data = Hachimi::instance().localized_data.load();
+    let parser = template::Parser::new(&template_filters::LIST);
+    check_dicts(&localized_data, &ld_path, &parser);
+
+    if let Some(assets_dir) = &localized_data.config.assets_dir {
+        for layer_path in overlay_paths.iter().chain(std::iter::once(&ld_path)) {
+            let assets_path = layer_path.join(assets_dir);
+            check_assets_dir(&assets_path, &assets_path, &parser);
+        }
+    }
+
+    finish();
+}
+
+fn finish() -> ! {
+    let count = ERROR_COUNT.load(atomic::Ordering::Relaxed);
+    if count != 0 {
+        eprintln!("{} error(s) found", count);
+        process::exit(1);
+    }
+    eprintln!("No errors found");
+    process::exit(0);
+}
+
+fn check_config(ld_path: &Path) {
+    let config_path = ld_path.join("config.json");
+    let json = match fs::read_to_string(&config_path) {
+        Ok(v) => v,
+        Err(e) => {
+            error!("Failed to read '{}': {}", config_path.display(), e);
+            return;
+        }
+    };
+    let config: LocalizedDataConfig = match serde_json::from_str(&json) {
+        Ok(v) => v,
+        Err(e) => {
+            report("config.json", "", e);
+            return;
+        }
+    };
+
+    type ParseFn = fn(&str) -> Result<plurals::Resolver, crate::core::Error>;
+    let forms: [(&str, &Option<String>, ParseFn); 2] = [
+        ("plural_form", &config.plural_form, plurals::Resolver::parse_plural_form),
+        ("ordinal_form", &config.ordinal_form, plurals::Resolver::parse_ordinal_form)
+    ];
+    for (key, form, parse) in forms {
+        if let Some(form) = form {
+            if let Err(e) = parse(form) {
+                report("config.json", key, e);
+            }
+        }
+    }
+
+    const NATIVE_SQL_TABLES: &[&str] = &["text_data", "character_system_text", "race_jikkyo_comment", "race_jikkyo_message"];
+    for (i, table) in config.sql_tables.iter().enumerate() {
+        let key = format!("sql_tables.{}", i);
+        if NATIVE_SQL_TABLES.contains(&table.table.as_str()) {
+            report("config.json", &key, format!("table '{}' is handled natively and can't be redeclared", table.table));
+        }
+        else if config.sql_tables[..i].iter().any(|t| t.table == table.table) {
+            report("config.json", &key, format!("table '{}' is declared more than once", table.table));
+        }
+        if table.key_columns.is_empty() {
+            report("config.json", &key, "no key columns");
+        }
+    }
+}
+
+fn check_template(parser: &template::Parser, file: &str, key: &str, text: &str, extra_filters: &[&str]) {
+    check_template_ex(parser, file, key, text, extra_filters, &[]);
+}
+
+fn check_template_ex(parser: &template::Parser, file: &str, key: &str, text: &str, extra_filters: &[&str], extra_vars: &[&str]) {
+    for e in parser.validate(text, extra_filters, extra_vars) {
+        report(file, key, e);
+    }
+}
+
+/// The layer files of a dict, so that entries are reported against the file they were actually
+/// loaded from (an overlay or a fallback pack) rather than the main dict file.
+struct DictSources {
+    layers: Vec<(String, serde_json::Value)>,
+    default: String
+}
+
+impl DictSources {
+    fn new(localized_data: &LocalizedData, ld_path: &Path, dict_name: &dyn Fn(&LocalizedDataConfig) -> Option<&String>) -> DictSources {
+        let layers = localized_data.dict_layer_files(dict_name).into_iter()
+            .filter_map(|path| {
+                // Unparsable layers have already been reported while loading
+                let value = LocalizedData::parse_dict_file::<serde_json::Value>(&path).ok()?;
+                let name = path.strip_prefix(ld_path).unwrap_or(&path).display().to_string();
+                Some((name, value))
+            })
+            .collect();
+        DictSources {
+            layers,
+            default: dict_name(&localized_data.config).cloned().unwrap_or_default()
+        }
+    }
+
+    fn find(&self, pred: impl Fn(&serde_json::Value) -> bool) -> &str {
+        self.layers.iter()
+            .find(|(_, value)| pred(value))
+            .map(|(name, _)| name.as_str())
+            .unwrap_or(&self.default)
+    }
+
+    fn file(&self, key: &str) -> &str {
+        self.find(|v| v.get(key).is_some())
+    }
+
+    fn nested_file(&self, key: &str, sub_key: &str) -> &str {
+        self.find(|v| v.get(key).and_then(|d| d.get(sub_key)).is_some())
+    }
+
+    fn pattern_file(&self, pattern: &str) -> &str {
+        self.find(|v| v.as_array().is_some_and(|entries|
+            entries.iter().any(|e| e.get("pattern").and_then(|p| p.as_str()) == Some(pattern))
+        ))
+    }
+}
+
+fn check_dicts(localized_data: &LocalizedData, ld_path: &Path, parser: &template::Parser) {
+    let config = &localized_data.config;
+    let tg_filters = &template_filters::TEXT_GENERATOR_FILTERS;
+    let sources = |dict_name: &dyn Fn(&LocalizedDataConfig) -> Option<&String>| {
+        DictSources::new(localized_data, ld_path, dict_name)
+    };
+
+    if let Some(format) = &config.month_text_format {
+        check_template_ex(parser, "config.json", "month_text_format", format, &["month", "half"], &["month", "half"]);
+    }
+
+    if config.localize_dict.is_some() {
+        let sources = sources(&|c| c.localize_dict.as_ref());
+        for (key, text) in &localized_data.localize_dict {
+            check_template(parser, sources.file(key), key, text, tg_filters);
+        }
+    }
+
+    if config.hashed_dict.is_some() {
+        let sources = sources(&|c| c.hashed_dict.as_ref());
+        for (key, text) in &localized_data.hashed_dict {
+            let key = key.to_string();
+            check_template(parser, sources.file(&key), &key, text, tg_filters);
+        }
+    }
+
+    type DictNameFn = fn(&LocalizedDataConfig) -> Option<&String>;
+    let nested_dicts: [(DictNameFn, _); 2] = [
+        (|c| c.text_data_dict.as_ref(), &localized_data.text_data_dict),
+        (|c| c.character_system_text_dict.as_ref(), &localized_data.character_system_text_dict)
+    ];
+    for (dict_name, dict) in nested_dicts {
+        if dict_name(config).is_none() { continue }
+        let sources = sources(&dict_name);
+        for (key, sub_dict) in dict {
+            let key = key.to_string();
+            for (sub_key, text) in sub_dict {
+                let sub_key = sub_key.to_string();
+                let file = sources.nested_file(&key, &sub_key);
+                check_template(parser, file, &format!("{}.{}", key, sub_key), text, tg_filters);
+            }
+        }
+    }
+
+    if config.glossary_dict.is_some() {
+        let sources = sources(&|c| c.glossary_dict.as_ref());
+        for (term, translation) in localized_data.glossary.terms() {
+            if term.is_empty() || translation.is_empty() {
+                report(sources.file(term), term, "empty glossary term or translation");
+            }
+        }
+    }
+
+    if config.pattern_dict.is_some() {
+        let sources = sources(&|c| c.pattern_dict.as_ref());
+        for (regex, text) in localized_data.pattern_dict.patterns() {
+            let vars = pattern_dict::capture_var_names(regex);
+            let vars: Vec<&str> = vars.iter().map(|s| s.as_str()).collect();
+            // Compiled as ^(?:pattern)$
+            let pattern = regex.as_str().strip_prefix("^(?:").and_then(|p| p.strip_suffix(")$")).unwrap_or(regex.as_str());
+            check_template_ex(parser, sources.pattern_file(pattern), regex.as_str(), text, tg_filters, &vars);
+        }
+    }
+
+    // Evaluated without any context
+    let jikkyo_dicts: [(DictNameFn, _); 2] = [
+        (|c| c.race_jikkyo_comment_dict.as_ref(), &localized_data.race_jikkyo_comment_dict),
+        (|c| c.race_jikkyo_message_dict.as_ref(), &localized_data.race_jikkyo_message_dict)
+    ];
+    for (dict_name, dict) in jikkyo_dicts {
+        if dict_name(config).is_none() { continue }
+        let sources = sources(&dict_name);
+        for (key, text) in dict {
+            let key = key.to_string();
+            check_template(parser, sources.file(&key), &key, text, &[]);
+        }
+    }
+
+    for table in config.sql_tables.iter() {
+        let Some(dict) = localized_data.sql_table_dicts.get(&table.table) else { continue };
+        let sources = sources(&|c| c.sql_tables.iter().find(|t| t.table == table.table).map(|t| &t.dict));
+        for (key, text) in dict {
+            let file = sources.file(key);
+            if key.split(',').count() != table.key_columns.len() {
+                report(file, key, format!("expected {} key values", table.key_columns.len()));
+            }
+            check_template(parser, file, key, text, tg_filters);
+        }
+    }
+}
+
+fn check_assets_dir(assets_path: &Path, dir: &Path, parser: &template::Parser) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            check_assets_dir(assets_path, &path, parser);
+            continue;
+        }
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+
+        let rel_path = path.strip_prefix(assets_path).unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+        check_asset_dict(&path, &rel_path, parser);
+    }
+}
+
+fn parse_asset_dict<T: DeserializeOwned>(path: &Path, rel_path: &str) -> Option<T> {
+    let json = match fs::read_to_string(path) {
+        Ok(v) => v,
+        Err(e) => {
+            report(rel_path, "", e);
+            return None;
+        }
+    };
+    match serde_json::from_str(&json) {
+        Ok(v) => Some(v),
+        Err(e) => {
+            report(rel_path, "", e);
+            None
+        }
+    }
+}
+
+fn check_asset_dict(path: &Path, rel_path: &str, parser: &template::Parser) {
+    let tg_filters = &template_filters::TEXT_GENERATOR_FILTERS;
+    let file_name = rel_path.rsplit('/').next().unwrap_or_default();
+
+    if file_name.starts_with("storytimeline_") || file_name.starts_with("hometimeline_") {
+        let Some(dict): Option<StoryTimelineDataDict> = parse_asset_dict(path, rel_path) else { return };
+        if let Some(title) = &dict.title {
+            check_template(parser, rel_path, "title", title, tg_filters);
+        }
+        for (i, block) in dict.text_block_list.iter().enumerate() {
+            let texts = block.name.iter()
+                .chain(block.text.iter())
+                .chain(block.choice_data_list.iter())
+                .chain(block.color_text_info_list.iter());
+            for text in texts {
+                check_template(parser, rel_path, &format!("text_block_list.{}", i), text, tg_filters);
+            }
+        }
+    }
+    else if rel_path.starts_with("race/storyrace/text/") {
+        let Some(dict): Option<StoryRaceTextDict> = parse_asset_dict(path, rel_path) else { return };
+        for (i, text) in dict.iter().enumerate() {
+            check_template(parser, rel_path, &i.to_string(), text.text(), tg_filters);
+        }
+    }
+    else if rel_path.starts_with("lyrics/") {
+        let Some(dict): Option<LyricsDict> = parse_asset_dict(path, rel_path) else { return };
+        for (time, text) in dict.iter() {
+            check_template(parser, rel_path, &time.to_string(), text.text(), tg_filters);
+        }
+    }
+    else if rel_path.starts_with("uianimation/flash/") {
+        let Some(info): Option<AssetInfo<AnRootData>> = parse_asset_dict(path, rel_path) else { return };
+        if let Some(data) = &info.data {
+            check_an_root(parser, rel_path, data);
+        }
+    }
+    else if rel_path.starts_with("uianimation/flashcombine/") {
+        let Some(info): Option<AssetInfo<FlashActionPlayerData>> = parse_asset_dict(path, rel_path) else { return };
+        if let Some(data) = info.data.as_ref().and_then(|d| d.an_root.as_ref()) {
+            check_an_root(parser, rel_path, data);
+        }
+    }
+    else {
+        // Texture metadata etc.
+        _ = parse_asset_dict::<AssetInfo<IgnoredAny>>(path, rel_path);
+    }
+}
+
+fn check_an_root(parser: &template::Parser, rel_path: &str, data: &AnRootData) {
+    for (i, motion_param) in data.motion_parameter_list.iter() {
+        for (j, text_param) in motion_param.text_param_list.iter() {
+            if let Some(text) = &text_param.text {
+                let key = format!("motion_parameter_list.{}.text_param_list.{}", i, j);
+                check_template(parser, rel_path, &key, text, &template_filters::TEXT_GENERATOR_FILTERS);
+            }
+        }
+    }
+}